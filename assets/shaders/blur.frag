@@ -20,17 +20,7 @@ float gaussian(in float x, in float sigma) {
     return INV_SQRT_2PI * exp(-0.5 * x * x / (sigma * sigma)) / sigma;
 }
 
-vec4 premult(in vec4 color) {
-    return vec4(color.rgb * color.a, color.a);
-}
-
-vec4 unpremult(in vec4 color) {
-    // Prevent division by zero
-    if (color.a == 0.0)
-        return vec4(0.0);
-
-    return vec4(color.rgb / color.a, color.a);
-}
+#include "common/premult.glsl"
 
 // Transparency-aware blur
 vec4 blur(in sampler2D image, in vec2 direction, in vec2 uv) {
@@ -0,0 +1,37 @@
+#version 330 core
+
+// Axis of the pass, in texels.
+uniform vec2 u_direction;
+// Half-width of the box, in texels. The outermost texels are weighted by its
+// fractional part so that it can vary smoothly.
+uniform float u_radius;
+
+uniform sampler2D u_tex;
+
+in vec2 v_uv;
+
+out vec4 FragColor;
+
+#include "common/premult.glsl"
+
+// Transparency-aware box blur along one axis
+vec4 box(in sampler2D image, in vec2 direction, in vec2 uv) {
+    int n = int(floor(u_radius));
+    float edge = u_radius - float(n);
+    vec2 texel = direction / textureSize(image, 0);
+
+    vec4 sum = premult(texture(image, uv));
+    for (int i = 1; i <= n; ++i) {
+        sum += premult(texture(image, uv + texel * float(i)));
+        sum += premult(texture(image, uv - texel * float(i)));
+    }
+
+    sum += premult(texture(image, uv + texel * float(n + 1))) * edge;
+    sum += premult(texture(image, uv - texel * float(n + 1))) * edge;
+
+    return unpremult(sum / (1.0 + 2.0 * u_radius));
+}
+
+void main() {
+    FragColor = box(u_tex, u_direction, v_uv);
+}
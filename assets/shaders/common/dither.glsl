@@ -1,11 +1,3 @@
-#version 330 core
-
-uniform sampler2D u_texture;
-
-in vec2 v_uv;
-
-out vec4 FragColor;
-
 // uniform pdf rand [0;1[
 vec4 hash43n(vec3 p) {
     p = fract(p * vec3(5.3987, 5.4421, 6.9371));
@@ -28,7 +20,3 @@ vec4 dither(vec4 c) {
 
     return c + rnd / 255.0;
 }
-
-void main() {
-    FragColor = dither(texture(u_texture, v_uv));
-}
@@ -0,0 +1,11 @@
+vec4 premult(in vec4 color) {
+    return vec4(color.rgb * color.a, color.a);
+}
+
+vec4 unpremult(in vec4 color) {
+    // Prevent division by zero
+    if (color.a == 0.0)
+        return vec4(0.0);
+
+    return vec4(color.rgb / color.a, color.a);
+}
@@ -0,0 +1,30 @@
+// Shape of the round quads, shared by their vertex shaders and round-rect.frag.
+
+// Bits of a quad's style.
+const uint GRADIENT_MASK = 3u;
+const uint GRADIENT_LINEAR = 1u;
+const uint GRADIENT_RADIAL = 2u;
+const uint INSET_SHADOW = 4u;
+
+// Modified based on https://iquilezles.org/articles/distfunctions2d/
+// That website is very handy
+//
+// The radii are those of the top-left, top-right, bottom-right and bottom-left
+// corners, with y going down.
+float sd_rounded_box(vec2 pos, vec2 size, vec4 radii) {
+    vec2 side = pos.x > 0.0 ? radii.yz : radii.xw;
+    float radius = pos.y > 0.0 ? side.y : side.x;
+
+    vec2 q = abs(pos) - size * 0.5 + radius;
+    return min(max(q.x, q.y), 0.0) + length(max(q, 0.0)) - radius;
+}
+
+// How far an outer shadow reaches past the box on every side, so that the quad
+// can be grown to cover it. Inset shadows stay inside.
+float shadow_margin(float shadow_alpha, uint style, vec2 offset, float blur) {
+    if (shadow_alpha == 0.0 || (style & INSET_SHADOW) != 0u) {
+        return 0.0;
+    }
+
+    return 3.0 * blur + length(offset);
+}
@@ -0,0 +1,13 @@
+#version 330 core
+precision mediump float;
+
+uniform sampler2D u_font;
+
+in vec2 v_uv;
+in vec4 v_color;
+
+out vec4 FragColor;
+
+void main() {
+    FragColor = v_color * texture(u_font, v_uv);
+}
@@ -0,0 +1,19 @@
+#version 330 core
+precision mediump float;
+
+// in pixels, from the top-left corner
+uniform vec2 u_screen_size;
+
+in vec2 position;
+in vec2 uv;
+in vec4 color;
+
+out vec2 v_uv;
+out vec4 v_color;
+
+void main() {
+    vec2 ndc = position / u_screen_size * 2.0 - 1.0;
+    gl_Position = vec4(ndc.x, -ndc.y, 0.0, 1.0);
+    v_uv = uv;
+    v_color = color;
+}
@@ -1,7 +1,11 @@
-#version 330
+#version 330 core
 precision mediump float;
 
+// Without SCREEN_SPACE, positions are transformed by the camera. With it,
+// they're already in normalized device coordinates.
+#ifndef SCREEN_SPACE
 uniform mat4 u_mvp;
+#endif
 
 in vec2 position;
 in vec2 uv;
@@ -9,6 +13,10 @@ in vec2 uv;
 out vec2 v_uv;
 
 void main() {
+#ifdef SCREEN_SPACE
+    gl_Position = vec4(position, 0.0, 1.0);
+#else
     gl_Position = u_mvp * vec4(position, 0.0, 1.0);
+#endif
     v_uv = uv;
 }
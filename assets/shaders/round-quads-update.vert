@@ -0,0 +1,61 @@
+#version 330
+
+// Spins the quads around the mouse, one vertex per quad. Nothing is drawn: the
+// next_* outputs are captured by transform feedback into the buffer that the
+// quads are drawn from, with the same layout as the input.
+
+uniform vec2 u_mouse_pos;
+uniform float u_surround_radius;
+uniform float u_dt;
+
+in vec2 position;
+in vec2 size;
+in float rotation;
+in vec4 corner_radii;
+in float border_width;
+in uint fill_color;
+in uint gradient_color;
+in float gradient_angle;
+in uint style;
+in uint stroke_color;
+in uint shadow_color;
+in vec2 shadow_offset;
+in float shadow_blur;
+in float intensity;
+
+out vec2 next_position;
+out vec2 next_size;
+out float next_rotation;
+out vec4 next_corner_radii;
+out float next_border_width;
+flat out uint next_fill_color;
+flat out uint next_gradient_color;
+out float next_gradient_angle;
+flat out uint next_style;
+flat out uint next_stroke_color;
+flat out uint next_shadow_color;
+out vec2 next_shadow_offset;
+out float next_shadow_blur;
+out float next_intensity;
+
+const float PI = 3.14159265358979;
+
+void main() {
+    float distance = distance(position, u_mouse_pos);
+    float closeness = max(u_surround_radius - distance, 0.0) / u_surround_radius;
+
+    next_position = position;
+    next_size = size;
+    next_rotation = rotation + (u_dt * PI) * 2.0 * closeness;
+    next_corner_radii = corner_radii;
+    next_border_width = border_width;
+    next_fill_color = fill_color;
+    next_gradient_color = gradient_color;
+    next_gradient_angle = gradient_angle;
+    next_style = style;
+    next_stroke_color = stroke_color;
+    next_shadow_color = shadow_color;
+    next_shadow_offset = shadow_offset;
+    next_shadow_blur = shadow_blur;
+    next_intensity = 2.0 * closeness + 0.5;
+}
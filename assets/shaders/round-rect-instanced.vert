@@ -0,0 +1,89 @@
+#version 330
+precision mediump float;
+
+// Same as round-rect.vert, but with one record per quad instead of per vertex.
+// The corners are expanded here from gl_VertexID.
+
+uniform mat4 u_mvp;
+// Index of the selected quad, or -1.
+uniform int u_selected;
+
+in vec2 position;
+in vec2 size;
+in float rotation;
+in vec4 corner_radii;
+in float border_width;
+in uint fill_color;
+in uint gradient_color;
+in float gradient_angle;
+in uint style;
+in uint stroke_color;
+in uint shadow_color;
+// In world coordinates, so that shadows don't spin with the quads.
+in vec2 shadow_offset;
+in float shadow_blur;
+in float intensity;
+
+out vec2 v_pos;
+out vec2 v_size;
+out vec4 v_corner_radii;
+out float v_border_width;
+out vec4 v_fill_color;
+out vec4 v_gradient_color;
+out float v_gradient_angle;
+flat out uint v_style;
+out vec4 v_stroke_color;
+out vec4 v_shadow_color;
+out vec2 v_shadow_offset;
+out float v_shadow_blur;
+out float v_intensity;
+
+#include "common/round-rect.glsl"
+
+const vec4 SELECTED_STROKE_COLOR = vec4(1.0, 0.85, 0.2, 1.0);
+
+const vec2[4] uvs = vec2[4](
+        vec2(-0.5, -0.5),
+        vec2(-0.5, 0.5),
+        vec2(0.5, 0.5),
+        vec2(0.5, -0.5)
+    );
+
+// RGBA packed in little-endian order, red in the lowest byte.
+vec4 unpack_color(uint color) {
+    uvec4 bytes = uvec4(color, color >> 8u, color >> 16u, color >> 24u) & 0xffu;
+    return vec4(bytes) / 255.0;
+}
+
+vec2 rotate(vec2 v, vec2 r) {
+    return vec2(v.x * r.x - v.y * r.y, v.y * r.x + v.x * r.y);
+}
+
+void main() {
+    vec4 shadow = unpack_color(shadow_color);
+    float margin = shadow_margin(shadow.a, style, shadow_offset, shadow_blur);
+
+    vec2 uv = uvs[gl_VertexID % 4];
+    vec2 r = vec2(cos(rotation), sin(rotation));
+    vec2 corner = uv * (size + 2.0 * margin);
+
+    gl_Position = u_mvp * vec4(position + rotate(corner, r), 0.0, 1.0);
+    v_pos = corner;
+    v_size = size;
+    v_corner_radii = corner_radii;
+    v_border_width = border_width;
+    v_fill_color = unpack_color(fill_color);
+    v_gradient_color = unpack_color(gradient_color);
+    v_gradient_angle = gradient_angle;
+    v_style = style;
+    v_stroke_color = unpack_color(stroke_color);
+    v_shadow_color = shadow;
+    v_shadow_offset = rotate(shadow_offset, vec2(r.x, -r.y));
+    v_shadow_blur = shadow_blur;
+    v_intensity = intensity;
+
+    if (gl_InstanceID == u_selected) {
+        v_stroke_color = SELECTED_STROKE_COLOR;
+        v_intensity = max(intensity, 1.5);
+    }
+}
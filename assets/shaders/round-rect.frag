@@ -1,42 +1,104 @@
 #version 330
 precision mediump float;
 
-in vec2 v_uv;
+// Position from the center of the quad, in its frame.
+in vec2 v_pos;
 in vec2 v_size;
+in vec4 v_corner_radii;
+in float v_border_width;
 in vec4 v_fill_color;
+in vec4 v_gradient_color;
+in float v_gradient_angle;
+flat in uint v_style;
 in vec4 v_stroke_color;
-in float v_border_radius;
-in float v_border_width;
+in vec4 v_shadow_color;
+in vec2 v_shadow_offset;
+in float v_shadow_blur;
 in float v_intensity;
 
 out vec4 FragColor;
 
-// Modified based on https://iquilezles.org/articles/distfunctions2d/
-// That website is very handy
-float sd_rounded_box(vec2 pos, vec2 size, float radius) {
-    vec2 q = abs(pos) - size * 0.5 + radius;
-    return min(max(q.x, q.y), 0.0) + length(max(q, 0.0)) - radius;
+#include "common/premult.glsl"
+#include "common/round-rect.glsl"
+
+// Approximation of the error function, with a maximum error of 5e-4.
+// See https://en.wikipedia.org/wiki/Error_function#Numerical_approximations
+float erf(float x) {
+    float a = abs(x);
+    float d = 1.0 + (0.278393 + (0.230389 + 0.078108 * a * a) * a) * a;
+    d *= d;
+    return sign(x) * (1.0 - 1.0 / (d * d));
+}
+
+// Coverage of the box blurred by a Gaussian, from the distance to it. This is
+// exact for a straight edge, and close enough around the corners as long as
+// the blur is small compared to the box, like the blurred box trick.
+float blurred_coverage(float dist, float sigma) {
+    if (sigma <= 0.0) {
+        return step(dist, 0.0);
+    }
+
+    return 0.5 - 0.5 * erf(dist / (sqrt(2.0) * sigma));
+}
+
+vec4 fill_color(vec2 pos) {
+    uint gradient = v_style & GRADIENT_MASK;
+
+    float t;
+    if (gradient == GRADIENT_LINEAR) {
+        // from one side of the box to the other along the direction
+        vec2 dir = vec2(cos(v_gradient_angle), sin(v_gradient_angle));
+        t = dot(pos, dir) / dot(abs(dir), v_size) + 0.5;
+    } else if (gradient == GRADIENT_RADIAL) {
+        // from the center to the middle of the sides
+        t = length(pos / (v_size * 0.5));
+    } else {
+        return v_fill_color;
+    }
+
+    return mix(v_fill_color, v_gradient_color, clamp(t, 0.0, 1.0));
 }
 
 void main() {
-    vec2 pos = v_uv * v_size;
+    vec2 pos = v_pos;
 
-    float dist = sd_rounded_box(pos, v_size, v_border_radius);
+    float dist = sd_rounded_box(pos, v_size, v_corner_radii);
     float delta = fwidth(dist);
 
-    if (dist > 0.0) {
-        discard;
+    bool has_shadow = v_shadow_color.a > 0.0;
+    bool inset = (v_style & INSET_SHADOW) != 0u;
+
+    vec4 inner_color = fill_color(pos);
+    if (has_shadow && inset) {
+        // the shadow falls where the shifted box doesn't cover
+        float shadow_dist = sd_rounded_box(pos - v_shadow_offset, v_size, v_corner_radii);
+        float shadow = 1.0 - blurred_coverage(shadow_dist, v_shadow_blur);
+        inner_color.rgb = mix(inner_color.rgb, v_shadow_color.rgb, shadow * v_shadow_color.a);
     }
 
     vec4 frag_color = mix(
             mix(
-                v_fill_color,
+                inner_color,
                 v_stroke_color,
                 smoothstep(-v_border_width - delta, -v_border_width, dist)
             ),
             vec4(v_stroke_color.rgb, 0.0),
             smoothstep(-delta, 0.0, dist)
         );
+    frag_color.rgb *= v_intensity;
+
+    if (has_shadow && !inset) {
+        // the quad over its shadow
+        float shadow_dist = sd_rounded_box(pos - v_shadow_offset, v_size, v_corner_radii);
+        float shadow = blurred_coverage(shadow_dist, v_shadow_blur) * v_shadow_color.a;
+
+        vec4 quad = premult(frag_color);
+        frag_color = unpremult(quad + premult(vec4(v_shadow_color.rgb, shadow)) * (1.0 - quad.a));
+    }
+
+    if (frag_color.a <= 0.0) {
+        discard;
+    }
 
-    FragColor = vec4(frag_color.rgb * v_intensity, frag_color.a);
+    FragColor = frag_color;
 }
@@ -2,23 +2,42 @@
 precision mediump float;
 
 uniform mat4 u_mvp;
+// Index of the selected quad, or -1.
+uniform int u_selected;
 
 in vec2 position;
 in vec2 size;
+in vec4 corner_radii;
+in float border_width;
 in vec4 fill_color;
+in vec4 gradient_color;
+in float gradient_angle;
+in uint style;
 in vec4 stroke_color;
-in float border_radius;
-in float border_width;
+in vec4 shadow_color;
+// In the frame of the quad.
+in vec2 shadow_offset;
+in float shadow_blur;
 in float intensity;
 
-out vec2 v_uv;
+out vec2 v_pos;
 out vec2 v_size;
+out vec4 v_corner_radii;
+out float v_border_width;
 out vec4 v_fill_color;
+out vec4 v_gradient_color;
+out float v_gradient_angle;
+flat out uint v_style;
 out vec4 v_stroke_color;
-out float v_border_radius;
-out float v_border_width;
+out vec4 v_shadow_color;
+out vec2 v_shadow_offset;
+out float v_shadow_blur;
 out float v_intensity;
 
+#include "common/round-rect.glsl"
+
+const vec4 SELECTED_STROKE_COLOR = vec4(1.0, 0.85, 0.2, 1.0);
+
 const vec2[4] uvs = vec2[4](
         vec2(-0.5, -0.5),
         vec2(-0.5, 0.5),
@@ -27,12 +46,26 @@ const vec2[4] uvs = vec2[4](
     );
 
 void main() {
+    // the corners were already grown around the shadow on the CPU
+    float margin = shadow_margin(shadow_color.a, style, shadow_offset, shadow_blur);
+
     gl_Position = u_mvp * vec4(position, 0.0, 1.0);
-    v_uv = uvs[gl_VertexID % 4];
+    v_pos = uvs[gl_VertexID % 4] * (size + 2.0 * margin);
     v_size = size;
+    v_corner_radii = corner_radii;
+    v_border_width = border_width;
     v_fill_color = fill_color;
+    v_gradient_color = gradient_color;
+    v_gradient_angle = gradient_angle;
+    v_style = style;
     v_stroke_color = stroke_color;
-    v_border_radius = border_radius;
-    v_border_width = border_width;
+    v_shadow_color = shadow_color;
+    v_shadow_offset = shadow_offset;
+    v_shadow_blur = shadow_blur;
     v_intensity = intensity;
+
+    if (gl_VertexID / 4 == u_selected) {
+        v_stroke_color = SELECTED_STROKE_COLOR;
+        v_intensity = max(intensity, 1.5);
+    }
 }
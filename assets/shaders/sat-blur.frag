@@ -0,0 +1,59 @@
+#version 330 core
+
+// Box blur of any radius at a constant cost, reading the four corners of the
+// box in a summed-area table.
+
+// Half-width of the box, in texels.
+uniform float u_radius;
+// Grow the radius from 0 at the center to u_radius in the corners.
+uniform bool u_radial;
+
+// Summed-area table built by sat-build.frag.
+uniform sampler2D u_tex;
+
+in vec2 v_uv;
+
+out vec4 FragColor;
+
+#include "common/premult.glsl"
+
+vec4 fetch(ivec2 p) {
+    if (p.x < 0 || p.y < 0) {
+        return vec4(0.0);
+    }
+
+    return texelFetch(u_tex, min(p, textureSize(u_tex, 0) - 1), 0);
+}
+
+// Sum of the texels before a point, in texels. It's interpolated between texel
+// corners in full precision, as texture filtering may not be.
+vec4 sum_before(vec2 p) {
+    vec2 q = p - 1.0;
+    ivec2 i = ivec2(floor(q));
+    vec2 f = q - floor(q);
+
+    vec4 a = fetch(i);
+    vec4 b = fetch(i + ivec2(1, 0));
+    vec4 c = fetch(i + ivec2(0, 1));
+    vec4 d = fetch(i + ivec2(1, 1));
+    return mix(mix(a, b, f.x), mix(c, d, f.x), f.y);
+}
+
+void main() {
+    vec2 size = vec2(textureSize(u_tex, 0));
+
+    float radius = u_radius;
+    if (u_radial) {
+        radius *= length(v_uv - 0.5) / length(vec2(0.5));
+    }
+
+    vec2 center = v_uv * size;
+    vec2 lo = clamp(center - 0.5 - radius, vec2(0.0), size);
+    vec2 hi = clamp(center + 0.5 + radius, vec2(0.0), size);
+    vec2 extent = hi - lo;
+
+    vec4 sum = sum_before(hi) - sum_before(vec2(lo.x, hi.y))
+        - sum_before(vec2(hi.x, lo.y)) + sum_before(lo);
+
+    FragColor = unpremult(sum / (extent.x * extent.y) + 0.5);
+}
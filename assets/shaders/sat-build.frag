@@ -0,0 +1,35 @@
+#version 330 core
+
+// Builds a summed-area table by recursive doubling: every pass adds the texel
+// `u_offset` texels before, so after log2(size) passes along an axis each texel
+// holds the sum of all the texels before it on that axis.
+
+uniform ivec2 u_offset;
+// The first pass reads the image instead of a partial table.
+uniform bool u_first;
+
+uniform sampler2D u_tex;
+
+in vec2 v_uv;
+
+out vec4 FragColor;
+
+#include "common/premult.glsl"
+
+vec4 fetch(ivec2 p) {
+    vec4 texel = texelFetch(u_tex, p, 0);
+
+    // sums of values centered on zero stay smaller, and so more precise
+    return u_first ? premult(texel) - 0.5 : texel;
+}
+
+void main() {
+    // the table is the size of the image
+    ivec2 p = ivec2(v_uv * vec2(textureSize(u_tex, 0)));
+    ivec2 before = p - u_offset;
+
+    FragColor = fetch(p);
+    if (before.x >= 0 && before.y >= 0) {
+        FragColor += fetch(before);
+    }
+}
@@ -7,6 +7,14 @@ out vec4 FragColor;
 
 uniform sampler2D u_texture;
 
+#ifdef DITHER
+#include "common/dither.glsl"
+#endif
+
 void main() {
+#ifdef DITHER
+    FragColor = dither(texture(u_texture, v_uv));
+#else
     FragColor = texture(u_texture, v_uv);
+#endif
 }
@@ -0,0 +1,189 @@
+//! Measures how much the blur scenes band in each framebuffer format, by
+//! rendering them headless and comparing the results against a reference
+//! blurred in 32-bit floats.
+//!
+//! Dithering hides banding by trading it for noise, so the raw error isn't
+//! enough to compare formats. The error is also smoothed to remove the noise,
+//! and what remains is the banding that can still be seen.
+
+use std::{error::Error, fmt};
+
+use glam::UVec2;
+use image::RgbaImage;
+
+use crate::{common_gl::ColorFormat, headless::HeadlessContext, scenes::SceneOptions};
+
+/// Radius of the box filter applied to the error, wide enough to average out
+/// the dithering noise but not the bands.
+const SMOOTHING_RADIUS: usize = 3;
+
+const REFERENCE_FORMAT: ColorFormat = ColorFormat::Rgba32F;
+
+/// Error of a render against the reference, in 8-bit levels.
+#[derive(Debug, Clone, Copy)]
+pub struct BandingStats {
+    /// RMS of the error, including the noise added by dithering.
+    pub error_rms: f32,
+    /// RMS of the smoothed error.
+    pub banding_rms: f32,
+    /// Largest smoothed error.
+    pub banding_max: f32,
+}
+
+impl BandingStats {
+    /// Compares the color channels of two renders of the same size.
+    pub fn compare(reference: &RgbaImage, image: &RgbaImage) -> Self {
+        assert_eq!(reference.dimensions(), image.dimensions());
+
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let mut error_sum = 0.0;
+        let mut banding_sum = 0.0;
+        let mut banding_max = 0.0f32;
+
+        for channel in 0..3 {
+            let error = (reference.pixels().zip(image.pixels()))
+                .map(|(a, b)| b.0[channel] as f32 - a.0[channel] as f32)
+                .collect::<Vec<_>>();
+            let smoothed = box_blur(&error, width, height);
+
+            error_sum += error.iter().map(|e| e * e).sum::<f32>();
+            banding_sum += smoothed.iter().map(|e| e * e).sum::<f32>();
+            banding_max = (smoothed.iter()).fold(banding_max, |max, e| max.max(e.abs()));
+        }
+
+        let count = (3 * width * height) as f32;
+        Self {
+            error_rms: (error_sum / count).sqrt(),
+            banding_rms: (banding_sum / count).sqrt(),
+            banding_max,
+        }
+    }
+}
+
+/// Averages each value with its neighbors in a square of side
+/// `2 * SMOOTHING_RADIUS + 1`, cut at the edges.
+fn box_blur(values: &[f32], width: usize, height: usize) -> Vec<f32> {
+    let window = |i: usize, len: usize| {
+        i.saturating_sub(SMOOTHING_RADIUS)..(i + SMOOTHING_RADIUS + 1).min(len)
+    };
+
+    let mut horizontal = vec![0.0; values.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let xs = window(x, width);
+            let count = xs.len() as f32;
+            horizontal[y * width + x] = xs.map(|x| values[y * width + x]).sum::<f32>() / count;
+        }
+    }
+
+    let mut blurred = vec![0.0; values.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let ys = window(y, height);
+            let count = ys.len() as f32;
+            blurred[y * width + x] = ys.map(|y| horizontal[y * width + x]).sum::<f32>() / count;
+        }
+    }
+
+    blurred
+}
+
+/// Banding of a scene in every format, with and without dithering.
+pub struct BandingReport {
+    pub scene: String,
+    pub rows: Vec<(ColorFormat, bool, BandingStats)>,
+    /// Formats the driver can't render to, with the one the scene fell back
+    /// to. They have no rows.
+    pub unsupported: Vec<(ColorFormat, ColorFormat)>,
+}
+
+impl BandingReport {
+    pub fn measure(
+        context: &HeadlessContext,
+        scene: &str,
+        options: &SceneOptions,
+        size: UVec2,
+        frames: u32,
+    ) -> Result<Self, Box<dyn Error>> {
+        let render = |format, dither| {
+            let mut options = options.clone();
+            options.blur.format = Some(format);
+            options.blur.dither = Some(dither);
+            context.render_with_format(scene, &options, size, frames)
+        };
+
+        // the scene falls back to RGBA8 when the driver can't render to a
+        // format, which would make RGBA8 look perfect
+        let (reference, format) = render(REFERENCE_FORMAT, false)?;
+        match format {
+            Some(REFERENCE_FORMAT) => (),
+            Some(format) => {
+                return Err(format!(
+                    "{scene} rendered the reference in {format} instead of {REFERENCE_FORMAT}, \
+                     which the driver can't render to"
+                )
+                .into())
+            }
+            None => {
+                return Err(format!("{scene} doesn't blur, there is no banding to measure").into())
+            }
+        }
+
+        let mut rows = Vec::new();
+        let mut unsupported = Vec::new();
+        'formats: for format in ColorFormat::ALL {
+            for dither in [false, true] {
+                let (image, rendered_format) = render(format, dither)?;
+                let rendered_format = rendered_format.unwrap_or(format);
+                if rendered_format != format {
+                    unsupported.push((format, rendered_format));
+                    continue 'formats;
+                }
+
+                rows.push((format, dither, BandingStats::compare(&reference, &image)));
+            }
+        }
+
+        Ok(Self {
+            scene: scene.to_string(),
+            rows,
+            unsupported,
+        })
+    }
+}
+
+impl fmt::Display for BandingReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} against {REFERENCE_FORMAT} without dithering, in 8-bit levels:",
+            self.scene
+        )?;
+        writeln!(
+            f,
+            "{:<8} {:<6} {:>9} {:>11} {:>11}",
+            "format", "dither", "error rms", "banding rms", "banding max"
+        )?;
+
+        for (format, dither, stats) in &self.rows {
+            writeln!(
+                f,
+                "{:<8} {:<6} {:>9.3} {:>11.3} {:>11.3}",
+                format.to_string(),
+                if *dither { "on" } else { "off" },
+                stats.error_rms,
+                stats.banding_rms,
+                stats.banding_max,
+            )?;
+        }
+
+        for (format, rendered_format) in &self.unsupported {
+            writeln!(
+                f,
+                "{format} skipped, the driver can't render to it and {rendered_format} was used"
+            )?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,164 @@
+//! Saving rendered frames to disk.
+
+use std::{
+    error::Error,
+    io::{self, Write as _},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use glam::UVec2;
+use image::RgbaImage;
+
+pub const SCREENSHOT_DIR: &str = "screenshots";
+pub const RECORDING_DIR: &str = "recordings";
+
+/// Frame rate of recordings. The frame clock runs at a fixed step of
+/// `1 / RECORDING_FPS` while recording, however long a frame takes to render.
+pub const RECORDING_FPS: u32 = 60;
+
+/// Saves a screenshot in [`SCREENSHOT_DIR`], named after the scene and its
+/// parameters so that it can be reproduced later.
+pub fn save_screenshot(
+    image: &RgbaImage,
+    scene: &str,
+    params: &str,
+) -> Result<PathBuf, Box<dyn Error>> {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+    let file_name = format!("{}_{timestamp}.png", capture_name(scene, params));
+
+    let path = Path::new(SCREENSHOT_DIR).join(file_name);
+    std::fs::create_dir_all(SCREENSHOT_DIR)?;
+    image.save(&path)?;
+
+    Ok(path)
+}
+
+/// Records every frame it is given, either by piping raw RGBA to an `ffmpeg`
+/// child process, or as a numbered PNG sequence when `ffmpeg` isn't installed.
+pub struct Recording {
+    sink: RecordingSink,
+    size: UVec2,
+    frame_count: u32,
+}
+
+enum RecordingSink {
+    Ffmpeg { child: Child, path: PathBuf },
+    Frames { dir: PathBuf },
+}
+
+impl Recording {
+    /// Starts a recording in [`RECORDING_DIR`], named like screenshots.
+    pub fn start(scene: &str, params: &str, size: UVec2) -> Result<Self, Box<dyn Error>> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+        let name = format!("{}_{timestamp}", capture_name(scene, params));
+        std::fs::create_dir_all(RECORDING_DIR)?;
+
+        let path = Path::new(RECORDING_DIR).join(format!("{name}.mp4"));
+        let sink = match spawn_ffmpeg(&path, size) {
+            Ok(child) => RecordingSink::Ffmpeg { child, path },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let dir = Path::new(RECORDING_DIR).join(name);
+                std::fs::create_dir_all(&dir)?;
+                RecordingSink::Frames { dir }
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Self {
+            sink,
+            size,
+            frame_count: 0,
+        })
+    }
+
+    pub fn size(&self) -> UVec2 {
+        self.size
+    }
+
+    pub fn frame_count(&self) -> u32 {
+        self.frame_count
+    }
+
+    pub fn push_frame(&mut self, image: &RgbaImage) -> Result<(), Box<dyn Error>> {
+        if image.dimensions() != (self.size.x, self.size.y) {
+            let (width, height) = image.dimensions();
+            return Err(format!(
+                "frame size changed from {}x{} to {width}x{height} during recording",
+                self.size.x, self.size.y
+            )
+            .into());
+        }
+
+        match &mut self.sink {
+            RecordingSink::Ffmpeg { child, .. } => {
+                let stdin = child.stdin.as_mut().ok_or("ffmpeg stdin is closed")?;
+                stdin.write_all(image.as_raw())?;
+            }
+            RecordingSink::Frames { dir } => {
+                image.save(dir.join(format!("frame_{:05}.png", self.frame_count)))?;
+            }
+        }
+
+        self.frame_count += 1;
+        Ok(())
+    }
+
+    /// Stops the recording and returns where it was written.
+    pub fn finish(self) -> Result<PathBuf, Box<dyn Error>> {
+        match self.sink {
+            RecordingSink::Ffmpeg { mut child, path } => {
+                // closing stdin lets ffmpeg finalize the file
+                drop(child.stdin.take());
+
+                let status = child.wait()?;
+                if !status.success() {
+                    return Err(format!("ffmpeg exited with {status}").into());
+                }
+
+                Ok(path)
+            }
+            RecordingSink::Frames { dir } => Ok(dir),
+        }
+    }
+}
+
+fn spawn_ffmpeg(path: &Path, size: UVec2) -> io::Result<Child> {
+    #[rustfmt::skip]
+    let args = [
+        "-loglevel", "error", "-y",
+        "-f", "rawvideo", "-pixel_format", "rgba",
+        "-video_size", &format!("{}x{}", size.x, size.y),
+        "-framerate", &RECORDING_FPS.to_string(),
+        "-i", "-",
+        // yuv420p needs even dimensions
+        "-vf", "crop=trunc(iw/2)*2:trunc(ih/2)*2",
+        "-c:v", "libx264", "-pix_fmt", "yuv420p",
+    ];
+
+    Command::new("ffmpeg")
+        .args(args)
+        .arg(path)
+        .stdin(Stdio::piped())
+        .spawn()
+}
+
+/// File-name-friendly version of a scene name and its parameters, for example
+/// `blurring_k5_r2.00_l4_vert-horz_dithering`.
+pub fn capture_name(scene: &str, params: &str) -> String {
+    let words = scene.split_whitespace().chain(params.split_whitespace());
+
+    let words = words.map(|word| {
+        (word.chars())
+            .filter(|&c| c != '=')
+            .map(|c| match c {
+                'a'..='z' | '0'..='9' | '.' => c,
+                'A'..='Z' => c.to_ascii_lowercase(),
+                _ => '-',
+            })
+            .collect::<String>()
+    });
+
+    words.collect::<Vec<_>>().join("_")
+}
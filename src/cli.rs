@@ -0,0 +1,281 @@
+//! Command-line arguments.
+
+use std::path::PathBuf;
+
+use glam::{uvec2, UVec2};
+
+use crate::{
+    common_gl::ColorFormat,
+    frame_clock::ClockMode,
+    scenes::{
+        round_quads::{QuadMode, QuadStyle, DEFAULT_STRESS_BUDGET_MS},
+        InputImage, SceneOptions,
+    },
+};
+
+const USAGE: &str = "\
+Usage: opengl-playground [OPTIONS]
+
+Options:
+  --scene <NAME>      Scene to start on
+  --size <WxH>        Window size, or output size in headless mode
+  --vsync <on|off>    Wait for vertical sync between frames [default: on, off with --stress]
+  --no-vsync          Same as --vsync off
+  --fixed-step <SEC>  Advance animations by the same step every frame instead of real time
+  --paused            Start with animations paused
+  --quads <N>         Number of quads in the Round Quads scene
+  --quad-mode <MODE>  How Round Quads are drawn: vertices, instanced or gpu [default: vertices]
+  --quad-style <NAME> Corners, fill and shadow of Round Quads: plain, cards or random [default: plain]
+  --stress [MS]       Double the Round Quads until frames take longer than MS, then report the maximum [default: 16.67]
+  --seed <N>          Seed for scenes with random content
+  --kernel <N>        Blur kernel size
+  --radius <R>        Blur radius, or Kawase distance
+  --layers <N>        Number of blur downsampling layers
+  --dither <on|off>   Dither the blurred image
+  --blur-format <FMT> Format the image is blurred in: rgba8, rgba16f or rgba32f [default: rgba8]
+  --image <PATH>      Image to blur instead of the bundled one
+  --headless <PNG>    Render offscreen without a window, then write the last frame to <PNG>
+  --frames <N>        Number of frames to render in headless mode [default: 1]
+  --measure-banding   Render the scene headless in every blur format and print how much each bands
+  -h, --help          Print this help
+";
+
+#[derive(Debug, Clone)]
+pub struct Args {
+    pub scene: Option<String>,
+    pub size: Option<UVec2>,
+    pub vsync: bool,
+    pub clock_mode: ClockMode,
+    pub scene_options: SceneOptions,
+    pub headless: Option<PathBuf>,
+    pub frames: u32,
+    pub measure_banding: bool,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Self {
+            scene: None,
+            size: None,
+            vsync: true,
+            clock_mode: ClockMode::RealTime,
+            scene_options: SceneOptions::default(),
+            headless: None,
+            frames: 1,
+            measure_banding: false,
+        }
+    }
+}
+
+impl Args {
+    /// Parses the process arguments, exiting on `--help` or on invalid arguments.
+    pub fn parse() -> Self {
+        let args = std::env::args().skip(1).collect::<Vec<_>>();
+
+        if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+            print!("{USAGE}");
+            std::process::exit(0);
+        }
+
+        match Self::try_parse(args) {
+            Ok(args) => args,
+            Err(e) => {
+                eprintln!("Error: {e}\n\n{USAGE}");
+                std::process::exit(2);
+            }
+        }
+    }
+
+    pub fn try_parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter().peekable();
+        let mut vsync = None;
+
+        while let Some(arg) = args.next() {
+            // accept both `--flag value` and `--flag=value`
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+
+            let mut value = || {
+                (inline_value.clone())
+                    .or_else(|| args.next())
+                    .ok_or_else(|| format!("missing value for {flag}"))
+            };
+
+            let options = &mut parsed.scene_options;
+            match flag.as_str() {
+                "--scene" => parsed.scene = Some(value()?),
+                "--size" => parsed.size = Some(parse_size(&value()?)?),
+                "--vsync" => vsync = Some(parse_switch(&flag, &value()?)?),
+                "--no-vsync" => vsync = Some(false),
+                "--fixed-step" => {
+                    parsed.clock_mode = ClockMode::FixedStep(parse_number(&flag, &value()?)?)
+                }
+                "--paused" => parsed.clock_mode = ClockMode::Paused,
+                "--quads" => options.quad_count = Some(parse_number(&flag, &value()?)?),
+                "--quad-mode" => options.quad_mode = Some(parse_quad_mode(&flag, &value()?)?),
+                "--quad-style" => options.quad_style = Some(parse_quad_style(&flag, &value()?)?),
+                "--stress" => {
+                    // the budget is optional, so the next flag isn't taken for it
+                    let budget =
+                        inline_value.or_else(|| args.next_if(|arg| !arg.starts_with("--")));
+                    options.stress_budget_ms = Some(match budget {
+                        Some(budget) => parse_number(&flag, &budget)?,
+                        None => DEFAULT_STRESS_BUDGET_MS,
+                    });
+                }
+                "--seed" => options.seed = Some(parse_number(&flag, &value()?)?),
+                "--kernel" => options.blur.kernel = Some(parse_number(&flag, &value()?)?),
+                "--radius" => options.blur.radius = Some(parse_number(&flag, &value()?)?),
+                "--layers" => options.blur.layers = Some(parse_number(&flag, &value()?)?),
+                "--dither" => options.blur.dither = Some(parse_switch(&flag, &value()?)?),
+                "--blur-format" => options.blur.format = Some(parse_format(&flag, &value()?)?),
+                "--image" => options.image = InputImage::File(PathBuf::from(value()?)),
+                "--headless" => parsed.headless = Some(PathBuf::from(value()?)),
+                "--frames" => parsed.frames = parse_number(&flag, &value()?)?,
+                "--measure-banding" => parsed.measure_banding = true,
+                _ => return Err(format!("unknown argument {flag}")),
+            }
+        }
+
+        // vertical sync would pin every frame of the stress test to the display
+        let stress = parsed.scene_options.stress_budget_ms.is_some();
+        parsed.vsync = match vsync {
+            Some(true) if stress => return Err("--stress needs vsync off".to_string()),
+            Some(vsync) => vsync,
+            None => !stress,
+        };
+
+        Ok(parsed)
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    (value.parse()).map_err(|_| format!("invalid value for {flag}: {value}"))
+}
+
+fn parse_switch(flag: &str, value: &str) -> Result<bool, String> {
+    match value {
+        "on" | "true" | "yes" | "1" => Ok(true),
+        "off" | "false" | "no" | "0" => Ok(false),
+        _ => Err(format!(
+            "invalid value for {flag}: {value}, expected on or off"
+        )),
+    }
+}
+
+fn parse_format(flag: &str, value: &str) -> Result<ColorFormat, String> {
+    (ColorFormat::ALL.into_iter())
+        .find(|format| format.to_string().eq_ignore_ascii_case(value))
+        .ok_or_else(|| {
+            format!("invalid value for {flag}: {value}, expected rgba8, rgba16f or rgba32f")
+        })
+}
+
+fn parse_quad_mode(flag: &str, value: &str) -> Result<QuadMode, String> {
+    (QuadMode::ALL.into_iter())
+        .find(|mode| mode.to_string().eq_ignore_ascii_case(value))
+        .ok_or_else(|| {
+            format!("invalid value for {flag}: {value}, expected vertices, instanced or gpu")
+        })
+}
+
+fn parse_quad_style(flag: &str, value: &str) -> Result<QuadStyle, String> {
+    (QuadStyle::ALL.into_iter())
+        .find(|style| style.to_string().eq_ignore_ascii_case(value))
+        .ok_or_else(|| {
+            format!("invalid value for {flag}: {value}, expected plain, cards or random")
+        })
+}
+
+fn parse_size(value: &str) -> Result<UVec2, String> {
+    let (w, h) = (value.split_once(['x', 'X']))
+        .ok_or_else(|| format!("invalid size {value}, expected WxH"))?;
+
+    let size = uvec2(parse_number("--size", w)?, parse_number("--size", h)?);
+    if size.x == 0 || size.y == 0 {
+        return Err(format!("invalid size {value}, must not be empty"));
+    }
+
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::try_parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn defaults() {
+        let args = parse(&[]).unwrap();
+
+        assert_eq!(args.scene, None);
+        assert_eq!(args.size, None);
+        assert!(args.vsync);
+        assert_eq!(args.frames, 1);
+        assert!(!args.measure_banding);
+        assert_eq!(args.scene_options.stress_budget_ms, None);
+    }
+
+    #[test]
+    fn values() {
+        let args = parse(&["--scene", "kawase", "--size=640x360", "--quads", "500"]).unwrap();
+
+        assert_eq!(args.scene.as_deref(), Some("kawase"));
+        assert_eq!(args.size, Some(uvec2(640, 360)));
+        assert_eq!(args.scene_options.quad_count, Some(500));
+    }
+
+    #[test]
+    fn stress_budget() {
+        let budget = |args: &[&str]| parse(args).unwrap().scene_options.stress_budget_ms;
+
+        assert_eq!(budget(&["--stress", "8"]), Some(8.0));
+        assert_eq!(budget(&["--stress=8"]), Some(8.0));
+        assert_eq!(budget(&["--stress"]), Some(DEFAULT_STRESS_BUDGET_MS));
+
+        let args = parse(&["--stress", "--quad-mode", "gpu"]).unwrap();
+        assert_eq!(
+            args.scene_options.stress_budget_ms,
+            Some(DEFAULT_STRESS_BUDGET_MS)
+        );
+        assert_eq!(args.scene_options.quad_mode, Some(QuadMode::Gpu));
+
+        assert_eq!(
+            parse(&["--stress", "fast"]).unwrap_err(),
+            "invalid value for --stress: fast"
+        );
+    }
+
+    #[test]
+    fn vsync() {
+        let vsync = |args: &[&str]| parse(args).map(|args| args.vsync);
+
+        assert_eq!(vsync(&["--vsync", "off"]), Ok(false));
+        assert_eq!(vsync(&["--no-vsync"]), Ok(false));
+        assert_eq!(vsync(&["--stress"]), Ok(false));
+        assert_eq!(vsync(&["--stress", "--no-vsync"]), Ok(false));
+        assert_eq!(
+            vsync(&["--stress", "--vsync", "on"]),
+            Err("--stress needs vsync off".to_string())
+        );
+    }
+
+    #[test]
+    fn unknown_flag() {
+        assert_eq!(
+            parse(&["--blur", "4"]).unwrap_err(),
+            "unknown argument --blur"
+        );
+    }
+
+    #[test]
+    fn missing_value() {
+        assert_eq!(parse(&["--seed"]).unwrap_err(), "missing value for --seed");
+    }
+}
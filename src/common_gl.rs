@@ -1,18 +1,29 @@
 // come on it's just OpenGL
 #![allow(clippy::missing_safety_doc)]
 
-use std::ffi::CStr;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::error::Error;
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::mem;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
 use gl::types::{GLchar, GLenum, GLint, GLsizei, GLuint};
-use glam::UVec2;
+use glam::{IVec2, Mat4, UVec2, Vec2, Vec3, Vec4};
+use image::RgbaImage;
+
+use crate::gpu_profiler;
 
 // --- debugging ---
 
 // Set in main when checking for the GL_KHR_debug extension.
 pub static DEBUG_ENABLED: AtomicBool = AtomicBool::new(false);
 
+/// Opens a debug group, which is also a scope of the GPU profiler.
 pub unsafe fn push_debug_group(message: &CStr) {
+    gpu_profiler::begin_scope(message);
+
     if DEBUG_ENABLED.load(Ordering::Relaxed) {
         gl::PushDebugGroup(
             gl::DEBUG_SOURCE_APPLICATION,
@@ -27,114 +38,1395 @@ pub unsafe fn pop_debug_group() {
     if DEBUG_ENABLED.load(Ordering::Relaxed) {
         gl::PopDebugGroup();
     }
+
+    gpu_profiler::end_scope();
+}
+
+// --- screen target ---
+
+thread_local! {
+    // Framebuffer that scenes present their final image to. It's the window's
+    // default framebuffer, unless rendering headless.
+    static SCREEN_FRAMEBUFFER: Cell<GLuint> = const { Cell::new(0) };
+}
+
+pub fn set_screen_framebuffer(fbo: GLuint) {
+    SCREEN_FRAMEBUFFER.set(fbo);
+}
+
+pub unsafe fn bind_screen_framebuffer() {
+    gl::BindFramebuffer(gl::FRAMEBUFFER, SCREEN_FRAMEBUFFER.get());
+}
+
+// --- owned objects ---
+
+/// Kinds of GL objects that delete themselves when dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectKind {
+    Buffer,
+    VertexArray,
+    Texture,
+    Program,
+    Framebuffer,
+    Renderbuffer,
+}
+
+impl ObjectKind {
+    const ALL: [Self; 6] = [
+        Self::Buffer,
+        Self::VertexArray,
+        Self::Texture,
+        Self::Program,
+        Self::Framebuffer,
+        Self::Renderbuffer,
+    ];
+}
+
+impl fmt::Display for ObjectKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Buffer => "buffer",
+            Self::VertexArray => "vertex array",
+            Self::Texture => "texture",
+            Self::Program => "program",
+            Self::Framebuffer => "framebuffer",
+            Self::Renderbuffer => "renderbuffer",
+        })
+    }
+}
+
+static LIVE_OBJECTS: [AtomicUsize; ObjectKind::ALL.len()] =
+    [const { AtomicUsize::new(0) }; ObjectKind::ALL.len()];
+
+fn track_object(kind: ObjectKind) {
+    LIVE_OBJECTS[kind as usize].fetch_add(1, Ordering::Relaxed);
+}
+
+fn untrack_object(kind: ObjectKind) {
+    LIVE_OBJECTS[kind as usize].fetch_sub(1, Ordering::Relaxed);
+}
+
+/// Number of owned GL objects alive, for each kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LiveObjects([usize; ObjectKind::ALL.len()]);
+
+impl LiveObjects {
+    pub fn now() -> Self {
+        Self(
+            LIVE_OBJECTS
+                .each_ref()
+                .map(|count| count.load(Ordering::Relaxed)),
+        )
+    }
+
+    /// Objects alive now that weren't alive at `before`, by kind.
+    pub fn leaked_since(before: Self) -> Vec<(ObjectKind, usize)> {
+        let now = Self::now();
+
+        (ObjectKind::ALL.into_iter())
+            .map(|kind| {
+                let i = kind as usize;
+                (kind, now.0[i].saturating_sub(before.0[i]))
+            })
+            .filter(|&(_, count)| count > 0)
+            .collect()
+    }
+}
+
+/// Declares a type owning a GL object made with `glGen*` and deleted with
+/// `glDelete*`.
+macro_rules! owned_object {
+    ($(#[$meta:meta])* $name:ident, $gen:ident, $delete:ident) => {
+        $(#[$meta])*
+        #[derive(Debug)]
+        pub struct $name(GLuint);
+
+        impl $name {
+            pub unsafe fn new() -> Self {
+                let mut id = 0;
+                gl::$gen(1, &mut id);
+                track_object(ObjectKind::$name);
+                Self(id)
+            }
+
+            pub fn id(&self) -> GLuint {
+                self.0
+            }
+        }
+
+        impl Drop for $name {
+            fn drop(&mut self) {
+                unsafe { gl::$delete(1, &self.0) };
+                untrack_object(ObjectKind::$name);
+            }
+        }
+    };
+}
+
+owned_object!(Buffer, GenBuffers, DeleteBuffers);
+owned_object!(VertexArray, GenVertexArrays, DeleteVertexArrays);
+owned_object!(Texture, GenTextures, DeleteTextures);
+owned_object!(Renderbuffer, GenRenderbuffers, DeleteRenderbuffers);
+
+#[derive(Debug)]
+pub struct Program(GLuint);
+
+impl Program {
+    pub unsafe fn new() -> Self {
+        track_object(ObjectKind::Program);
+        Self(gl::CreateProgram())
+    }
+
+    pub fn id(&self) -> GLuint {
+        self.0
+    }
+}
+
+impl Drop for Program {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteProgram(self.0) };
+        untrack_object(ObjectKind::Program);
+    }
 }
 
 // --- shader compilation ---
 
-pub unsafe fn create_shader_program(vert_source: &[u8], frag_source: &[u8]) -> GLuint {
-    let vert_shader = gl::CreateShader(gl::VERTEX_SHADER);
-    {
-        let length = vert_source.len() as i32;
-        let source = vert_source.as_ptr() as *const i8;
-        gl::ShaderSource(vert_shader, 1, &source, &length);
-        gl::CompileShader(vert_shader);
+/// Number of source lines shown before and after each line reported in a
+/// compile log.
+const EXCERPT_CONTEXT: usize = 2;
+
+/// GLSL source with its includes resolved and its defines injected, ready to be
+/// compiled.
+#[derive(Debug, Clone)]
+pub struct ShaderCode {
+    pub text: String,
+    /// Every file the source is made of, indexed by the source string number
+    /// used in `#line` directives. The first one is the main file.
+    pub files: Vec<ShaderFile>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ShaderFile {
+    pub name: String,
+    pub source: String,
+}
+
+/// Resolves `#include "file"` lines and adds a `#define` for each of `defines`
+/// right after the `#version` line.
+///
+/// Every file is only included once, and only the main one can have a
+/// `#version`. `#line` directives are inserted around included files, so that
+/// line numbers in compile logs are those of the file they come from.
+pub fn preprocess_shader(
+    name: &str,
+    source: &[u8],
+    defines: &[(&str, &str)],
+    load_include: &dyn Fn(&str) -> Option<Vec<u8>>,
+) -> Result<ShaderCode, String> {
+    let mut code = ShaderCode {
+        text: String::new(),
+        files: Vec::new(),
+    };
+
+    code.append_file(name, source, defines, load_include)?;
+    Ok(code)
+}
+
+impl ShaderCode {
+    fn append_file(
+        &mut self,
+        name: &str,
+        source: &[u8],
+        defines: &[(&str, &str)],
+        load_include: &dyn Fn(&str) -> Option<Vec<u8>>,
+    ) -> Result<(), String> {
+        let index = self.files.len();
+        let source = String::from_utf8_lossy(source).into_owned();
+
+        // pushed before its includes, so that it can't include itself
+        self.files.push(ShaderFile {
+            name: name.to_string(),
+            source: String::new(),
+        });
+
+        // `#version` has to stay the first line of the main file
+        let mut skipped_lines = 0;
+        if let Some(version) = (source.lines().next())
+            .filter(|l| l.trim().starts_with("#version"))
+            .filter(|_| index == 0)
+        {
+            self.text.push_str(version);
+            self.text.push('\n');
+            skipped_lines = 1;
+        }
+
+        for (name, value) in defines {
+            self.text.push_str(&format!("#define {name} {value}\n"));
+        }
+
+        self.text
+            .push_str(&format!("#line {} {index}\n", skipped_lines + 1));
+
+        for (i, line) in source.lines().enumerate().skip(skipped_lines) {
+            // it would end up in the middle of the main file
+            if index > 0 && line.trim().starts_with("#version") {
+                return Err(format!(
+                    "{name}:{}: only the main file can have a #version",
+                    i + 1
+                ));
+            }
+
+            let Some(include) = parse_include(line) else {
+                self.text.push_str(line);
+                self.text.push('\n');
+                continue;
+            };
+
+            let include = include.map_err(|e| format!("{name}:{}: {e}", i + 1))?;
+
+            if !self.files.iter().any(|file| file.name == include) {
+                let included = load_include(include)
+                    .ok_or_else(|| format!("{name}:{}: can't find {include:?}", i + 1))?;
+
+                self.append_file(include, &included, &[], load_include)?;
+            }
+
+            // back to the line after the include
+            self.text.push_str(&format!("#line {} {index}\n", i + 2));
+        }
+
+        self.files[index].source = source;
+        Ok(())
     }
-    verify_shader(vert_shader, "vert");
+}
+
+/// Name of the file included by a line, or `None` if it isn't an `#include`.
+fn parse_include(line: &str) -> Option<Result<&str, &'static str>> {
+    let rest = line.trim().strip_prefix('#')?.trim_start();
+    let rest = rest.strip_prefix("include")?.trim();
+
+    Some(
+        (rest.strip_prefix('"'))
+            .and_then(|rest| rest.strip_suffix('"'))
+            .filter(|name| !name.is_empty())
+            .ok_or("expected #include \"file\""),
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+    Link,
+}
+
+impl fmt::Display for ShaderStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Vertex => "vertex shader compile",
+            Self::Fragment => "fragment shader compile",
+            Self::Link => "program link",
+        })
+    }
+}
+
+/// A shader that failed to preprocess or compile, or a program that failed to
+/// link.
+#[derive(Debug, Clone)]
+pub struct ShaderError {
+    pub stage: ShaderStage,
+    /// Info log given by the driver, or the preprocessor error.
+    pub log: String,
+    /// Names of the vertex and fragment shaders, when the caller knows them.
+    pub names: Option<(&'static str, &'static str)>,
+    /// Source lines around each line number found in the log.
+    pub excerpts: Vec<SourceExcerpt>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SourceExcerpt {
+    /// File the line is in, which is an included one if it isn't the main
+    /// file of the shader.
+    pub file: String,
+    /// Line reported in the log, starting at 1.
+    pub line: usize,
+    /// Line numbers and contents of the surrounding lines.
+    pub lines: Vec<(usize, String)>,
+}
+
+impl ShaderError {
+    pub fn new(stage: ShaderStage, log: String, code: Option<&ShaderCode>) -> Self {
+        let files = code.map_or(&[][..], |code| &code.files);
+
+        let mut reported = log.lines().filter_map(reported_line).collect::<Vec<_>>();
+        reported.sort_unstable();
+        reported.dedup();
+
+        let excerpts = (reported.into_iter())
+            .filter_map(|(index, line)| {
+                let file = files.get(index)?;
+                let source_lines = file.source.lines().collect::<Vec<_>>();
+
+                if line == 0 || line > source_lines.len() {
+                    return None;
+                }
+
+                let first = line.saturating_sub(EXCERPT_CONTEXT).max(1);
+                let last = (line + EXCERPT_CONTEXT).min(source_lines.len());
+
+                Some(SourceExcerpt {
+                    file: file.name.clone(),
+                    line,
+                    lines: (first..=last)
+                        .map(|n| (n, source_lines[n - 1].to_string()))
+                        .collect(),
+                })
+            })
+            .collect();
+
+        Self {
+            stage,
+            log,
+            names: None,
+            excerpts,
+        }
+    }
+
+    /// Names the shaders in the error message.
+    pub fn with_names(mut self, vert: &'static str, frag: &'static str) -> Self {
+        self.names = Some((vert, frag));
+        self
+    }
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} error", self.stage)?;
 
-    let frag_shader = gl::CreateShader(gl::FRAGMENT_SHADER);
-    {
-        let length = frag_source.len() as i32;
-        let source = frag_source.as_ptr() as *const i8;
-        gl::ShaderSource(frag_shader, 1, &source, &length);
-        gl::CompileShader(frag_shader);
+        match (self.names, self.stage) {
+            (Some((vert, _)), ShaderStage::Vertex) => write!(f, " in {vert}")?,
+            (Some((_, frag)), ShaderStage::Fragment) => write!(f, " in {frag}")?,
+            (Some((vert, frag)), ShaderStage::Link) => write!(f, " in {vert} + {frag}")?,
+            (None, _) => (),
+        }
+
+        writeln!(f, ":")?;
+        write!(f, "{}", self.log.trim_end())?;
+
+        for excerpt in &self.excerpts {
+            write!(f, "\n\n  --> {}:{}", excerpt.file, excerpt.line)?;
+
+            for (number, text) in &excerpt.lines {
+                let marker = if *number == excerpt.line { '>' } else { ' ' };
+                write!(f, "\n{marker}{number:>4} | {text}")?;
+            }
+        }
+
+        Ok(())
     }
-    verify_shader(frag_shader, "frag");
+}
 
-    let program = gl::CreateProgram();
-    {
-        gl::AttachShader(program, vert_shader);
-        gl::AttachShader(program, frag_shader);
+impl Error for ShaderError {}
 
-        gl::LinkProgram(program);
-        gl::UseProgram(program);
+/// Finds the source string index and line number in a line of a compile log.
+/// Drivers don't agree on a format, but they all start with them, like
+/// `0:12(3): error` (Mesa), `0(12) : error` (Nvidia) or `ERROR: 0:12: ...`
+/// (AMD, Apple).
+fn reported_line(log_line: &str) -> Option<(usize, usize)> {
+    let start = log_line.find(|c: char| c.is_ascii_digit())?;
+    let rest = &log_line[start..];
 
+    let end = rest.find(|c: char| !c.is_ascii_digit())?;
+    let index = rest[..end].parse().ok()?;
+    let rest = rest[end..].strip_prefix([':', '('])?;
+
+    let end = rest.find(|c: char| !c.is_ascii_digit())?;
+    Some((index, rest[..end].parse().ok()?))
+}
+
+/// Compiles and links a program, or returns why it couldn't, without leaking
+/// any of the shaders.
+pub unsafe fn create_shader_program(
+    vert_code: &ShaderCode,
+    frag_code: &ShaderCode,
+) -> Result<ShaderProgram, ShaderError> {
+    let vert_shader = compile_shader(gl::VERTEX_SHADER, vert_code.text.as_bytes());
+    let frag_shader = compile_shader(gl::FRAGMENT_SHADER, frag_code.text.as_bytes());
+
+    let error = (shader_error(vert_shader))
+        .map(|log| ShaderError::new(ShaderStage::Vertex, log, Some(vert_code)))
+        .or_else(|| {
+            shader_error(frag_shader)
+                .map(|log| ShaderError::new(ShaderStage::Fragment, log, Some(frag_code)))
+        });
+
+    if let Some(error) = error {
         gl::DeleteShader(vert_shader);
         gl::DeleteShader(frag_shader);
+        return Err(error);
+    }
+
+    let program = Program::new();
+    gl::AttachShader(program.id(), vert_shader);
+    gl::AttachShader(program.id(), frag_shader);
+    gl::LinkProgram(program.id());
+
+    gl::DeleteShader(vert_shader);
+    gl::DeleteShader(frag_shader);
+
+    match program_error(program.id()) {
+        Some(log) => Err(ShaderError::new(ShaderStage::Link, log, None)),
+        None => {
+            gl::UseProgram(program.id());
+
+            let name = |code: &ShaderCode| code.files[0].name.clone();
+            let label = format!("{} + {}", name(vert_code), name(frag_code));
+            Ok(ShaderProgram::new(program, label))
+        }
+    }
+}
+
+/// Compiles and links a program without a fragment shader, whose vertex
+/// shader outputs are captured into a buffer by transform feedback, interleaved
+/// in the order of `varyings`.
+pub unsafe fn create_feedback_program(
+    vert_code: &ShaderCode,
+    varyings: &[impl AsRef<str>],
+) -> Result<ShaderProgram, ShaderError> {
+    let vert_shader = compile_shader(gl::VERTEX_SHADER, vert_code.text.as_bytes());
+
+    if let Some(log) = shader_error(vert_shader) {
+        gl::DeleteShader(vert_shader);
+        return Err(ShaderError::new(ShaderStage::Vertex, log, Some(vert_code)));
+    }
+
+    let program = Program::new();
+    gl::AttachShader(program.id(), vert_shader);
+
+    let varyings = (varyings.iter())
+        .map(|varying| CString::new(varying.as_ref()).unwrap())
+        .collect::<Vec<_>>();
+    let varying_ptrs = varyings.iter().map(|v| v.as_ptr()).collect::<Vec<_>>();
+    gl::TransformFeedbackVaryings(
+        program.id(),
+        varying_ptrs.len() as GLsizei,
+        varying_ptrs.as_ptr(),
+        gl::INTERLEAVED_ATTRIBS,
+    );
+
+    gl::LinkProgram(program.id());
+    gl::DeleteShader(vert_shader);
+
+    match program_error(program.id()) {
+        Some(log) => Err(ShaderError::new(ShaderStage::Link, log, None)),
+        None => {
+            gl::UseProgram(program.id());
+
+            let label = format!("{} (transform feedback)", vert_code.files[0].name);
+            Ok(ShaderProgram::new(program, label))
+        }
     }
-    verify_program(program);
+}
 
-    program
+unsafe fn compile_shader(ty: GLenum, source: &[u8]) -> GLuint {
+    let shader = gl::CreateShader(ty);
+    let length = source.len() as i32;
+    let source = source.as_ptr() as *const i8;
+    gl::ShaderSource(shader, 1, &source, &length);
+    gl::CompileShader(shader);
+    shader
 }
 
-pub unsafe fn verify_shader(shader: GLuint, ty: &str) {
+/// Info log of a shader that failed to compile.
+unsafe fn shader_error(shader: GLuint) -> Option<String> {
     let mut status = 0;
     gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut status);
 
-    if status != 1 {
-        let mut length = 0;
-        gl::GetShaderiv(shader, gl::INFO_LOG_LENGTH, &mut length);
+    if status == 1 {
+        return None;
+    }
+
+    let mut length = 0;
+    gl::GetShaderiv(shader, gl::INFO_LOG_LENGTH, &mut length);
+
+    let mut log = String::with_capacity(length as usize);
+    log.extend(std::iter::repeat_n('\0', length as usize));
+    gl::GetShaderInfoLog(shader, length, &mut length, log.as_mut_ptr().cast());
+    log.truncate(length as usize);
 
-        if length > 0 {
-            let mut log = String::with_capacity(length as usize);
-            log.extend(std::iter::repeat('\0').take(length as usize));
-            gl::GetShaderInfoLog(shader, length, &mut length, log.as_mut_ptr().cast());
-            log.truncate(length as usize);
+    Some(log)
+}
+
+/// Info log of a program that failed to link.
+unsafe fn program_error(program: GLuint) -> Option<String> {
+    let mut status = 0;
+    gl::GetProgramiv(program, gl::LINK_STATUS, &mut status);
+
+    if status == 1 {
+        return None;
+    }
+
+    let mut length = 0;
+    gl::GetProgramiv(program, gl::INFO_LOG_LENGTH, &mut length);
+
+    let mut log = String::with_capacity(length as usize);
+    log.extend(std::iter::repeat_n('\0', length as usize));
+    gl::GetProgramInfoLog(program, length, &mut length, log.as_mut_ptr().cast());
+    log.truncate(length as usize);
+
+    Some(log)
+}
+
+// --- shader programs ---
+
+/// Linked program, with the active uniforms and attributes it was queried for
+/// when created.
+#[derive(Debug)]
+pub struct ShaderProgram {
+    program: Program,
+    /// Names of the shaders, used in warnings.
+    pub label: String,
+    uniforms: Vec<ShaderVariable>,
+    attribs: Vec<ShaderVariable>,
+    /// Names that were already warned about, so that a typo in a uniform set
+    /// every frame is only reported once.
+    warned: RefCell<HashSet<String>>,
+}
+
+/// Active uniform or attribute of a program.
+#[derive(Debug, Clone)]
+pub struct ShaderVariable {
+    /// Name without the `[0]` suffix of arrays.
+    pub name: String,
+    pub location: GLint,
+    /// GLSL type, like `gl::FLOAT_VEC2`.
+    pub ty: GLenum,
+    /// Number of elements for arrays, 1 otherwise.
+    pub size: GLint,
+}
 
-            eprintln!("SHADER COMPILE ERROR ({ty}): {log}");
+impl ShaderProgram {
+    pub unsafe fn new(program: Program, label: String) -> Self {
+        Self {
+            uniforms: active_variables(program.id(), false),
+            attribs: active_variables(program.id(), true),
+            program,
+            label,
+            warned: RefCell::new(HashSet::new()),
+        }
+    }
+
+    pub fn id(&self) -> GLuint {
+        self.program.id()
+    }
+
+    pub unsafe fn bind(&self) {
+        gl::UseProgram(self.id());
+    }
+
+    pub fn uniforms(&self) -> &[ShaderVariable] {
+        &self.uniforms
+    }
+
+    pub fn attribs(&self) -> &[ShaderVariable] {
+        &self.attribs
+    }
+
+    /// Sets a uniform of the program, which has to be bound. Warns instead if
+    /// the program has no such uniform, or if it has another type.
+    pub unsafe fn set<T: UniformValue>(&self, name: &str, value: T) {
+        let Some(uniform) = self.uniforms.iter().find(|uniform| uniform.name == name) else {
+            self.warn(name, || format!("no active uniform {name:?}"));
+            return;
+        };
+
+        if !T::TYPES.contains(&uniform.ty) {
+            self.warn(name, || {
+                let expected = glsl_type_name(uniform.ty);
+                let given = glsl_type_name(T::TYPES[0]);
+                format!("uniform {name:?} is a {expected}, not a {given}")
+            });
+            return;
+        }
+
+        value.upload(uniform.location);
+    }
+
+    /// Location of an active attribute, warning if there's none.
+    pub fn attrib_location(&self, name: &str) -> Option<GLuint> {
+        match self.attribs.iter().find(|attrib| attrib.name == name) {
+            Some(attrib) => Some(attrib.location as GLuint),
+            None => {
+                self.warn(name, || format!("no active attribute {name:?}"));
+                None
+            }
+        }
+    }
+
+    /// Points the attributes of the program to the fields of `V`, in the
+    /// vertex buffer bound to `GL_ARRAY_BUFFER`. Fields without an attribute,
+    /// attributes without a field and attributes of another type are reported.
+    pub unsafe fn bind_vertex_layout<V: VertexLayout>(&self) {
+        self.bind_layout::<V>(0);
+    }
+
+    /// Like [`Self::bind_vertex_layout`], but the attributes advance once per
+    /// instance instead of once per vertex.
+    pub unsafe fn bind_instance_layout<V: VertexLayout>(&self) {
+        self.bind_layout::<V>(1);
+    }
+
+    unsafe fn bind_layout<V: VertexLayout>(&self, divisor: GLuint) {
+        let stride = mem::size_of::<V>() as GLsizei;
+
+        for field in V::ATTRIBS {
+            let Some(attrib) = self.attribs.iter().find(|attrib| attrib.name == field.name) else {
+                self.warn(field.name, || {
+                    format!("no active attribute for vertex field {:?}", field.name)
+                });
+                continue;
+            };
+
+            if attrib.ty != field.glsl_type {
+                self.warn(field.name, || {
+                    let expected = glsl_type_name(attrib.ty);
+                    let given = glsl_type_name(field.glsl_type);
+                    format!("attribute {:?} is a {expected}, not a {given}", field.name)
+                });
+                continue;
+            }
+
+            let location = attrib.location as GLuint;
+            if field.is_integer() {
+                gl::VertexAttribIPointer(
+                    location,
+                    field.components,
+                    field.component_type,
+                    stride,
+                    field.offset as *const _,
+                );
+            } else {
+                gl::VertexAttribPointer(
+                    location,
+                    field.components,
+                    field.component_type,
+                    gl::FALSE,
+                    stride,
+                    field.offset as *const _,
+                );
+            }
+            gl::VertexAttribDivisor(location, divisor);
+            gl::EnableVertexAttribArray(location);
+        }
+
+        for attrib in &self.attribs {
+            if !V::ATTRIBS.iter().any(|field| field.name == attrib.name) {
+                self.warn(&attrib.name, || {
+                    format!(
+                        "attribute {:?} has no field in the vertex layout",
+                        attrib.name
+                    )
+                });
+            }
+        }
+    }
+
+    /// Silences the warnings about `name`, like a vertex field that the
+    /// program doesn't read on purpose.
+    pub fn ignore(&self, name: &str) {
+        self.warned.borrow_mut().insert(name.to_string());
+    }
+
+    fn warn(&self, name: &str, message: impl FnOnce() -> String) {
+        if self.warned.borrow_mut().insert(name.to_string()) {
+            eprintln!("Warning: {} ({})", message(), self.label);
         }
     }
 }
 
-pub unsafe fn verify_program(shader: GLuint) {
-    let mut status = 0;
-    gl::GetProgramiv(shader, gl::LINK_STATUS, &mut status);
+/// Lists the active uniforms, or attributes, of a program. Uniforms in blocks
+/// and built-in attributes are left out, as they don't have a location.
+unsafe fn active_variables(program: GLuint, attribs: bool) -> Vec<ShaderVariable> {
+    let (count_param, max_length_param) = match attribs {
+        true => (gl::ACTIVE_ATTRIBUTES, gl::ACTIVE_ATTRIBUTE_MAX_LENGTH),
+        false => (gl::ACTIVE_UNIFORMS, gl::ACTIVE_UNIFORM_MAX_LENGTH),
+    };
+
+    let mut count = 0;
+    gl::GetProgramiv(program, count_param, &mut count);
+
+    let mut max_length = 0;
+    gl::GetProgramiv(program, max_length_param, &mut max_length);
+
+    let mut variables = Vec::with_capacity(count as usize);
+    let mut buffer = vec![0u8; max_length.max(1) as usize];
 
-    if status != 1 {
-        let mut length = 0;
-        gl::GetProgramiv(shader, gl::INFO_LOG_LENGTH, &mut length);
+    for index in 0..count as GLuint {
+        let (mut length, mut size, mut ty) = (0, 0, 0);
+        let name_ptr = buffer.as_mut_ptr().cast();
 
-        if length > 0 {
-            let mut log = String::with_capacity(length as usize);
-            log.extend(std::iter::repeat('\0').take(length as usize));
-            gl::GetProgramInfoLog(shader, length, &mut length, log.as_mut_ptr().cast());
-            log.truncate(length as usize);
+        let location = if attribs {
+            gl::GetActiveAttrib(
+                program,
+                index,
+                max_length,
+                &mut length,
+                &mut size,
+                &mut ty,
+                name_ptr,
+            );
+            gl::GetAttribLocation(program, name_ptr)
+        } else {
+            gl::GetActiveUniform(
+                program,
+                index,
+                max_length,
+                &mut length,
+                &mut size,
+                &mut ty,
+                name_ptr,
+            );
+            gl::GetUniformLocation(program, name_ptr)
+        };
 
-            eprintln!("PROGRAM LINK ERROR: {log}");
+        if location < 0 {
+            continue;
         }
+
+        let name = String::from_utf8_lossy(&buffer[..length as usize]);
+        let name = name.strip_suffix("[0]").unwrap_or(&name);
+
+        variables.push(ShaderVariable {
+            name: name.to_string(),
+            location,
+            ty,
+            size,
+        });
+    }
+
+    variables
+}
+
+fn glsl_type_name(ty: GLenum) -> &'static str {
+    match ty {
+        gl::FLOAT => "float",
+        gl::FLOAT_VEC2 => "vec2",
+        gl::FLOAT_VEC3 => "vec3",
+        gl::FLOAT_VEC4 => "vec4",
+        gl::FLOAT_MAT4 => "mat4",
+        gl::INT => "int",
+        gl::INT_VEC2 => "ivec2",
+        gl::UNSIGNED_INT => "uint",
+        gl::BOOL => "bool",
+        gl::SAMPLER_2D => "sampler2D",
+        _ => "unknown type",
     }
 }
 
+/// Value that can be given to [`ShaderProgram::set`].
+pub trait UniformValue {
+    /// GLSL types of the uniforms it can be given to, the most fitting first.
+    const TYPES: &'static [GLenum];
+
+    unsafe fn upload(&self, location: GLint);
+}
+
+impl UniformValue for f32 {
+    const TYPES: &'static [GLenum] = &[gl::FLOAT];
+
+    unsafe fn upload(&self, location: GLint) {
+        gl::Uniform1f(location, *self);
+    }
+}
+
+impl UniformValue for i32 {
+    // samplers are set to the index of their texture unit
+    const TYPES: &'static [GLenum] = &[gl::INT, gl::SAMPLER_2D];
+
+    unsafe fn upload(&self, location: GLint) {
+        gl::Uniform1i(location, *self);
+    }
+}
+
+impl UniformValue for u32 {
+    const TYPES: &'static [GLenum] = &[gl::UNSIGNED_INT];
+
+    unsafe fn upload(&self, location: GLint) {
+        gl::Uniform1ui(location, *self);
+    }
+}
+
+impl UniformValue for bool {
+    const TYPES: &'static [GLenum] = &[gl::BOOL];
+
+    unsafe fn upload(&self, location: GLint) {
+        gl::Uniform1i(location, *self as GLint);
+    }
+}
+
+impl UniformValue for Vec2 {
+    const TYPES: &'static [GLenum] = &[gl::FLOAT_VEC2];
+
+    unsafe fn upload(&self, location: GLint) {
+        gl::Uniform2f(location, self.x, self.y);
+    }
+}
+
+impl UniformValue for Vec3 {
+    const TYPES: &'static [GLenum] = &[gl::FLOAT_VEC3];
+
+    unsafe fn upload(&self, location: GLint) {
+        gl::Uniform3f(location, self.x, self.y, self.z);
+    }
+}
+
+impl UniformValue for Vec4 {
+    const TYPES: &'static [GLenum] = &[gl::FLOAT_VEC4];
+
+    unsafe fn upload(&self, location: GLint) {
+        gl::Uniform4f(location, self.x, self.y, self.z, self.w);
+    }
+}
+
+impl UniformValue for IVec2 {
+    const TYPES: &'static [GLenum] = &[gl::INT_VEC2];
+
+    unsafe fn upload(&self, location: GLint) {
+        gl::Uniform2i(location, self.x, self.y);
+    }
+}
+
+impl UniformValue for Mat4 {
+    const TYPES: &'static [GLenum] = &[gl::FLOAT_MAT4];
+
+    unsafe fn upload(&self, location: GLint) {
+        gl::UniformMatrix4fv(location, 1, gl::FALSE, self.as_ref().as_ptr());
+    }
+}
+
+// --- vertex layouts ---
+
+/// Field of a vertex struct, read by the attribute of the same name.
+#[derive(Debug, Clone, Copy)]
+pub struct VertexAttrib {
+    pub name: &'static str,
+    pub components: GLint,
+    /// Type of the components, like `gl::FLOAT`.
+    pub component_type: GLenum,
+    /// GLSL type of the attribute, like `gl::FLOAT_VEC2`.
+    pub glsl_type: GLenum,
+    /// Offset in bytes from the start of the vertex.
+    pub offset: usize,
+}
+
+impl VertexAttrib {
+    /// Integer attributes are read as integers by the shader, instead of being
+    /// converted to floats.
+    pub fn is_integer(&self) -> bool {
+        matches!(self.component_type, gl::INT | gl::UNSIGNED_INT)
+    }
+}
+
+/// Vertex struct whose fields can be bound to the attributes of a program
+/// with [`ShaderProgram::bind_vertex_layout`]. Implemented with
+/// [`vertex_layout!`].
+pub trait VertexLayout: Sized {
+    const ATTRIBS: &'static [VertexAttrib];
+}
+
+/// Type of a vertex struct field.
+pub trait VertexAttribType {
+    const COMPONENTS: GLint;
+    const COMPONENT_TYPE: GLenum;
+    const GLSL_TYPE: GLenum;
+}
+
+impl VertexAttribType for f32 {
+    const COMPONENTS: GLint = 1;
+    const COMPONENT_TYPE: GLenum = gl::FLOAT;
+    const GLSL_TYPE: GLenum = gl::FLOAT;
+}
+
+/// Read as a `uint`, like a packed color that the shader unpacks itself.
+impl VertexAttribType for u32 {
+    const COMPONENTS: GLint = 1;
+    const COMPONENT_TYPE: GLenum = gl::UNSIGNED_INT;
+    const GLSL_TYPE: GLenum = gl::UNSIGNED_INT;
+}
+
+impl VertexAttribType for Vec2 {
+    const COMPONENTS: GLint = 2;
+    const COMPONENT_TYPE: GLenum = gl::FLOAT;
+    const GLSL_TYPE: GLenum = gl::FLOAT_VEC2;
+}
+
+impl VertexAttribType for Vec3 {
+    const COMPONENTS: GLint = 3;
+    const COMPONENT_TYPE: GLenum = gl::FLOAT;
+    const GLSL_TYPE: GLenum = gl::FLOAT_VEC3;
+}
+
+impl VertexAttribType for Vec4 {
+    const COMPONENTS: GLint = 4;
+    const COMPONENT_TYPE: GLenum = gl::FLOAT;
+    const GLSL_TYPE: GLenum = gl::FLOAT_VEC4;
+}
+
+/// Declares a `#[repr(C)]` vertex struct and implements [`VertexLayout`] for
+/// it, with an attribute named after each field. Offsets come from the struct
+/// itself, so reordering its fields can't break the layout.
+///
+/// ```ignore
+/// vertex_layout! {
+///     #[derive(Debug, Clone, Copy, Default)]
+///     struct Vertex {
+///         position: Vec2,
+///         uv: Vec2,
+///     }
+/// }
+/// ```
+macro_rules! vertex_layout {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($field_vis:vis $field:ident: $ty:ty),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[repr(C)]
+        $vis struct $name {
+            $($field_vis $field: $ty),*
+        }
+
+        impl $crate::common_gl::VertexLayout for $name {
+            const ATTRIBS: &'static [$crate::common_gl::VertexAttrib] = &[
+                $($crate::common_gl::VertexAttrib {
+                    name: stringify!($field),
+                    components: <$ty as $crate::common_gl::VertexAttribType>::COMPONENTS,
+                    component_type: <$ty as $crate::common_gl::VertexAttribType>::COMPONENT_TYPE,
+                    glsl_type: <$ty as $crate::common_gl::VertexAttribType>::GLSL_TYPE,
+                    offset: ::std::mem::offset_of!($name, $field),
+                }),*
+            ];
+        }
+    };
+}
+
+pub(crate) use vertex_layout;
+
 // --- framebuffers and textures ---
 
-#[repr(C)]
+/// Pixel format of the color attachments of a framebuffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorFormat {
+    #[default]
+    Rgba8,
+    /// Half floats, which keep the precision of repeated blur passes.
+    Rgba16F,
+    Rgba32F,
+}
+
+impl ColorFormat {
+    pub const ALL: [Self; 3] = [Self::Rgba8, Self::Rgba16F, Self::Rgba32F];
+
+    fn internal_format(self) -> GLenum {
+        match self {
+            Self::Rgba8 => gl::RGBA8,
+            Self::Rgba16F => gl::RGBA16F,
+            Self::Rgba32F => gl::RGBA32F,
+        }
+    }
+
+    fn pixel_type(self) -> GLenum {
+        match self {
+            Self::Rgba8 => gl::UNSIGNED_BYTE,
+            Self::Rgba16F => gl::HALF_FLOAT,
+            Self::Rgba32F => gl::FLOAT,
+        }
+    }
+}
+
+impl fmt::Display for ColorFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Rgba8 => "RGBA8",
+            Self::Rgba16F => "RGBA16F",
+            Self::Rgba32F => "RGBA32F",
+        })
+    }
+}
+
+/// Format of the depth (and stencil) attachment of a framebuffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepthFormat {
+    Depth24,
+    Depth32F,
+    Depth24Stencil8,
+}
+
+impl DepthFormat {
+    fn internal_format(self) -> GLenum {
+        match self {
+            Self::Depth24 => gl::DEPTH_COMPONENT24,
+            Self::Depth32F => gl::DEPTH_COMPONENT32F,
+            Self::Depth24Stencil8 => gl::DEPTH24_STENCIL8,
+        }
+    }
+
+    fn attachment(self) -> GLenum {
+        match self {
+            Self::Depth24 | Self::Depth32F => gl::DEPTH_ATTACHMENT,
+            Self::Depth24Stencil8 => gl::DEPTH_STENCIL_ATTACHMENT,
+        }
+    }
+}
+
+/// Describes a framebuffer to create. By default it has a single RGBA8 color
+/// texture with linear filtering, clamped to its edges, and no depth.
 #[derive(Debug, Clone)]
+pub struct FramebufferDesc<'a> {
+    name: &'a str,
+    size: UVec2,
+    color_format: ColorFormat,
+    color_attachments: u32,
+    depth: Option<DepthFormat>,
+    samples: u32,
+    filter: GLenum,
+    wrap: GLenum,
+}
+
+impl<'a> FramebufferDesc<'a> {
+    pub fn new(name: &'a str, size: UVec2) -> Self {
+        Self {
+            name,
+            size,
+            color_format: ColorFormat::Rgba8,
+            color_attachments: 1,
+            depth: None,
+            samples: 1,
+            filter: gl::LINEAR,
+            wrap: gl::CLAMP_TO_EDGE,
+        }
+    }
+
+    pub fn color_format(self, color_format: ColorFormat) -> Self {
+        Self {
+            color_format,
+            ..self
+        }
+    }
+
+    /// Number of color attachments, all drawn to. Can be 0 for a depth-only
+    /// framebuffer.
+    pub fn color_attachments(self, color_attachments: u32) -> Self {
+        Self {
+            color_attachments,
+            ..self
+        }
+    }
+
+    pub fn depth(self, depth: DepthFormat) -> Self {
+        Self {
+            depth: Some(depth),
+            ..self
+        }
+    }
+
+    /// Multisampled framebuffers use renderbuffers instead of textures, and
+    /// have to be resolved into a single-sampled one to be read.
+    pub fn samples(self, samples: u32) -> Self {
+        Self {
+            samples: samples.max(1),
+            ..self
+        }
+    }
+
+    /// Minification and magnification filter of the color textures.
+    pub fn filter(self, filter: GLenum) -> Self {
+        Self { filter, ..self }
+    }
+
+    pub fn wrap(self, wrap: GLenum) -> Self {
+        Self { wrap, ..self }
+    }
+
+    pub unsafe fn create(&self) -> Result<Framebuffer, FramebufferError> {
+        let max_attachments =
+            get_integer(gl::MAX_COLOR_ATTACHMENTS).min(get_integer(gl::MAX_DRAW_BUFFERS)) as u32;
+        if self.color_attachments > max_attachments {
+            return Err(self.unsupported(format!(
+                "{} color attachments requested, at most {max_attachments} supported",
+                self.color_attachments
+            )));
+        }
+
+        let max_samples = get_integer(gl::MAX_SAMPLES) as u32;
+        if self.samples > max_samples {
+            return Err(self.unsupported(format!(
+                "{} samples requested, at most {max_samples} supported",
+                self.samples
+            )));
+        }
+
+        let mut fbo: GLuint = 0;
+        gl::GenFramebuffers(1, &mut fbo);
+        gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
+        track_object(ObjectKind::Framebuffer);
+
+        let mut fb = Framebuffer {
+            fbo,
+            textures: Vec::new(),
+            renderbuffers: Vec::new(),
+            size: self.size,
+            format: self.color_format,
+            color_attachments: self.color_attachments,
+            samples: self.samples,
+        };
+
+        let (width, height) = (self.size.x as GLsizei, self.size.y as GLsizei);
+        let draw_buffers = fb.draw_buffers();
+
+        for &attachment in &draw_buffers {
+            if self.samples > 1 {
+                let renderbuffer = self.renderbuffer(self.color_format.internal_format());
+                gl::FramebufferRenderbuffer(
+                    gl::FRAMEBUFFER,
+                    attachment,
+                    gl::RENDERBUFFER,
+                    renderbuffer.id(),
+                );
+                fb.renderbuffers.push(renderbuffer);
+            } else {
+                let texture = Texture::new();
+                gl::BindTexture(gl::TEXTURE_2D, texture.id());
+                gl::TexImage2D(
+                    gl::TEXTURE_2D,
+                    0,
+                    self.color_format.internal_format() as GLint,
+                    width,
+                    height,
+                    0,
+                    gl::RGBA,
+                    self.color_format.pixel_type(),
+                    std::ptr::null(),
+                );
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, self.filter as GLint);
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, self.filter as GLint);
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, self.wrap as GLint);
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, self.wrap as GLint);
+
+                gl::FramebufferTexture2D(
+                    gl::FRAMEBUFFER,
+                    attachment,
+                    gl::TEXTURE_2D,
+                    texture.id(),
+                    0,
+                );
+                fb.textures.push(texture);
+            }
+        }
+
+        if let Some(depth) = self.depth {
+            let renderbuffer = self.renderbuffer(depth.internal_format());
+            gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                depth.attachment(),
+                gl::RENDERBUFFER,
+                renderbuffer.id(),
+            );
+            fb.renderbuffers.push(renderbuffer);
+        }
+
+        gl::DrawBuffers(draw_buffers.len() as GLsizei, draw_buffers.as_ptr());
+        if draw_buffers.is_empty() {
+            gl::ReadBuffer(gl::NONE);
+        }
+
+        let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(FramebufferError::Incomplete {
+                name: self.name.to_string(),
+                size: self.size,
+                status,
+            });
+        }
+
+        Ok(fb)
+    }
+
+    unsafe fn renderbuffer(&self, internal_format: GLenum) -> Renderbuffer {
+        let renderbuffer = Renderbuffer::new();
+        gl::BindRenderbuffer(gl::RENDERBUFFER, renderbuffer.id());
+        gl::RenderbufferStorageMultisample(
+            gl::RENDERBUFFER,
+            // 0 samples for a single-sampled depth attachment
+            if self.samples > 1 { self.samples } else { 0 } as GLsizei,
+            internal_format,
+            self.size.x as GLsizei,
+            self.size.y as GLsizei,
+        );
+        renderbuffer
+    }
+
+    fn unsupported(&self, reason: String) -> FramebufferError {
+        FramebufferError::Unsupported {
+            name: self.name.to_string(),
+            reason,
+        }
+    }
+}
+
+/// Framebuffer with its attachments, all deleted when it's dropped.
+#[derive(Debug)]
 pub struct Framebuffer {
     pub fbo: GLuint,
-    pub texture: GLuint,
+    /// Color textures, one per attachment. Empty when multisampled.
+    pub textures: Vec<Texture>,
+    /// Multisampled color attachments, then the depth attachment if any.
+    renderbuffers: Vec<Renderbuffer>,
     pub size: UVec2,
+    pub format: ColorFormat,
+    pub color_attachments: u32,
+    pub samples: u32,
 }
 
-pub unsafe fn create_framebuffer(name: &str, size: UVec2) -> Framebuffer {
-    let mut fbo: GLuint = 0;
-    gl::GenFramebuffers(1, &mut fbo);
-    gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
+impl Framebuffer {
+    /// First color texture.
+    pub fn texture(&self) -> &Texture {
+        self.textures
+            .first()
+            .expect("framebuffer has no color texture, resolve it first if it's multisampled")
+    }
 
-    let mut texture: GLuint = 0;
-    gl::GenTextures(1, &mut texture);
-    upload_texture(texture, size.x, size.y, std::ptr::null(), gl::CLAMP_TO_EDGE);
-    gl::FramebufferTexture2D(
-        gl::FRAMEBUFFER,
-        gl::COLOR_ATTACHMENT0,
-        gl::TEXTURE_2D,
-        texture,
-        0,
-    );
+    /// Blits the color attachments into the ones of `target`, which resolves
+    /// the samples of a multisampled framebuffer. Both must be the same size.
+    pub unsafe fn resolve_into(&self, target: &Framebuffer) {
+        let (width, height) = (self.size.x as GLint, self.size.y as GLint);
+        let attachments = self.color_attachments.min(target.color_attachments);
+
+        gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.fbo);
+        gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, target.fbo);
+
+        for i in 0..attachments {
+            let attachment = gl::COLOR_ATTACHMENT0 + i;
+            gl::ReadBuffer(attachment);
+            gl::DrawBuffers(1, &attachment);
+            gl::BlitFramebuffer(
+                0,
+                0,
+                width,
+                height,
+                0,
+                0,
+                width,
+                height,
+                gl::COLOR_BUFFER_BIT,
+                gl::NEAREST,
+            );
+        }
+
+        // back to drawing to all the attachments of the target, like after
+        // create()
+        gl::ReadBuffer(gl::COLOR_ATTACHMENT0);
+        let draw_buffers = target.draw_buffers();
+        gl::DrawBuffers(draw_buffers.len() as GLsizei, draw_buffers.as_ptr());
+    }
+
+    fn draw_buffers(&self) -> Vec<GLenum> {
+        (0..self.color_attachments)
+            .map(|i| gl::COLOR_ATTACHMENT0 + i)
+            .collect()
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteFramebuffers(1, &self.fbo) };
+        untrack_object(ObjectKind::Framebuffer);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum FramebufferError {
+    /// `glCheckFramebufferStatus` didn't return `GL_FRAMEBUFFER_COMPLETE`,
+    /// usually because the driver can't render to a format.
+    Incomplete {
+        name: String,
+        size: UVec2,
+        status: GLenum,
+    },
+    /// More color attachments or samples than the driver supports.
+    Unsupported { name: String, reason: String },
+}
 
-    if gl::CheckFramebufferStatus(gl::FRAMEBUFFER) != gl::FRAMEBUFFER_COMPLETE {
-        eprintln!("{name} framebuffer ({}x{}) not complete", size.x, size.y);
+impl fmt::Display for FramebufferError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Incomplete { name, size, status } => {
+                let status = match *status {
+                    gl::FRAMEBUFFER_UNDEFINED => "GL_FRAMEBUFFER_UNDEFINED",
+                    gl::FRAMEBUFFER_INCOMPLETE_ATTACHMENT => "GL_FRAMEBUFFER_INCOMPLETE_ATTACHMENT",
+                    gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => {
+                        "GL_FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT"
+                    }
+                    gl::FRAMEBUFFER_UNSUPPORTED => "GL_FRAMEBUFFER_UNSUPPORTED",
+                    gl::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => {
+                        "GL_FRAMEBUFFER_INCOMPLETE_MULTISAMPLE"
+                    }
+                    _ => "unknown status",
+                };
+                write!(
+                    f,
+                    "{name} framebuffer ({}x{}) not complete: {status}",
+                    size.x, size.y
+                )
+            }
+            Self::Unsupported { name, reason } => {
+                write!(f, "{name} framebuffer not supported: {reason}")
+            }
+        }
     }
+}
 
-    Framebuffer { fbo, texture, size }
+impl Error for FramebufferError {}
+
+unsafe fn get_integer(name: GLenum) -> GLint {
+    let mut value = 0;
+    gl::GetIntegerv(name, &mut value);
+    value
+}
+
+/// Reads back the first color attachment of a framebuffer, top row first. It
+/// can't be multisampled.
+pub unsafe fn read_framebuffer(fb: &Framebuffer) -> RgbaImage {
+    read_pixels(fb.fbo, fb.size)
+}
+
+/// Reads back the framebuffer that scenes present to, top row first.
+pub unsafe fn read_screen_framebuffer(size: UVec2) -> RgbaImage {
+    read_pixels(SCREEN_FRAMEBUFFER.get(), size)
+}
+
+/// Reads back the color buffer of any framebuffer (0 being the window's
+/// default one), top row first.
+pub unsafe fn read_pixels(fbo: GLuint, size: UVec2) -> RgbaImage {
+    let mut pixels = vec![0u8; (size.x * size.y * 4) as usize];
+
+    gl::BindFramebuffer(gl::READ_FRAMEBUFFER, fbo);
+    gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+    gl::ReadPixels(
+        0,
+        0,
+        size.x as GLsizei,
+        size.y as GLsizei,
+        gl::RGBA,
+        gl::UNSIGNED_BYTE,
+        pixels.as_mut_ptr() as *mut _,
+    );
+
+    // OpenGL's origin is bottom-left
+    let image = RgbaImage::from_raw(size.x, size.y, pixels).unwrap();
+    image::imageops::flip_vertical(&image)
 }
 
 pub unsafe fn upload_texture(
@@ -161,3 +1453,133 @@ pub unsafe fn upload_texture(
     gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, clamp as GLint);
     gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, clamp as GLint);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILES: [(&str, &str); 3] = [
+        ("a.glsl", "#include \"b.glsl\"\nfloat a;"),
+        ("b.glsl", "float b;"),
+        ("version.glsl", "#version 330 core\nfloat v;"),
+    ];
+
+    fn preprocess(source: &str) -> Result<ShaderCode, String> {
+        let load_include = |name: &str| {
+            (FILES.iter())
+                .find(|(file, _)| *file == name)
+                .map(|(_, source)| source.as_bytes().to_vec())
+        };
+
+        preprocess_shader("main.vert", source.as_bytes(), &[], &load_include)
+    }
+
+    fn file_names(code: &ShaderCode) -> Vec<&str> {
+        code.files.iter().map(|file| file.name.as_str()).collect()
+    }
+
+    #[test]
+    fn nested_includes() {
+        let code = preprocess("#version 330 core\n#include \"a.glsl\"\nvoid main() {}").unwrap();
+
+        assert_eq!(file_names(&code), ["main.vert", "a.glsl", "b.glsl"]);
+        assert_eq!(
+            code.text,
+            "#version 330 core\n\
+             #line 2 0\n\
+             #line 1 1\n\
+             #line 1 2\n\
+             float b;\n\
+             #line 2 1\n\
+             float a;\n\
+             #line 3 0\n\
+             void main() {}\n"
+        );
+    }
+
+    #[test]
+    fn includes_each_file_once() {
+        let code = preprocess("#include \"b.glsl\"\n#include \"a.glsl\"\n").unwrap();
+
+        assert_eq!(file_names(&code), ["main.vert", "b.glsl", "a.glsl"]);
+        assert_eq!(code.text.matches("float b;").count(), 1);
+        assert_eq!(
+            code.text,
+            "#line 1 0\n\
+             #line 1 1\n\
+             float b;\n\
+             #line 2 0\n\
+             #line 1 2\n\
+             #line 2 2\n\
+             float a;\n\
+             #line 3 0\n"
+        );
+    }
+
+    #[test]
+    fn defines_after_version() {
+        let code = preprocess_shader(
+            "main.frag",
+            b"#version 330 core\nvoid main() {}",
+            &[("DITHER", "1")],
+            &|_| None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            code.text,
+            "#version 330 core\n#define DITHER 1\n#line 2 0\nvoid main() {}\n"
+        );
+    }
+
+    #[test]
+    fn rejects_version_in_include() {
+        let error = preprocess("#version 330 core\n#include \"version.glsl\"").unwrap_err();
+        assert_eq!(
+            error,
+            "version.glsl:1: only the main file can have a #version"
+        );
+    }
+
+    #[test]
+    fn missing_include() {
+        let error = preprocess("#version 330 core\n#include \"missing.glsl\"").unwrap_err();
+        assert_eq!(error, "main.vert:2: can't find \"missing.glsl\"");
+    }
+
+    #[test]
+    fn reported_line_formats() {
+        let mesa = "0:12(3): error: `color' undeclared";
+        let nvidia = "0(12) : error C1008: undefined variable \"color\"";
+        let amd = "ERROR: 1:12: 'color' : undeclared identifier";
+
+        assert_eq!(reported_line(mesa), Some((0, 12)));
+        assert_eq!(reported_line(nvidia), Some((0, 12)));
+        assert_eq!(reported_line(amd), Some((1, 12)));
+        assert_eq!(reported_line("ERROR: 1 compilation errors."), None);
+    }
+
+    #[test]
+    fn error_excerpts() {
+        let code = preprocess("#version 330 core\n#include \"a.glsl\"\nvoid main() {}").unwrap();
+        let log = "0:3(1): error: syntax error\n1:2(1): error: `a' redeclared\n".to_string();
+
+        let error = ShaderError::new(ShaderStage::Vertex, log, Some(&code));
+        let excerpts = (error.excerpts.iter())
+            .map(|excerpt| (excerpt.file.as_str(), excerpt.line, excerpt.lines.len()))
+            .collect::<Vec<_>>();
+
+        // cut at the first and last lines of each file
+        assert_eq!(excerpts, [("main.vert", 3, 3), ("a.glsl", 2, 2)]);
+    }
+
+    #[test]
+    fn error_out_of_range_lines() {
+        let code = preprocess("#version 330 core\nvoid main() {}").unwrap();
+        let log = "0:0(1): error\n0:99(1): error\n7:1(1): error\n".to_string();
+
+        let error = ShaderError::new(ShaderStage::Fragment, log, Some(&code));
+        assert!(error.excerpts.is_empty());
+        assert!(error.to_string().contains("0:99(1): error"));
+    }
+}
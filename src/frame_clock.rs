@@ -0,0 +1,139 @@
+//! Simulated clock that scenes and the scene controller read time from, so
+//! that animations can run at a fixed step, be paused or be stepped frame by
+//! frame regardless of the real frame rate.
+
+use std::time::Instant;
+
+/// Time step of a single step while paused, in seconds.
+pub const SINGLE_STEP: f32 = 1.0 / 60.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClockMode {
+    /// Follows the wall clock.
+    RealTime,
+    /// Advances by the same step (in seconds) every frame.
+    FixedStep(f32),
+    /// Doesn't advance, except by [`SINGLE_STEP`] when [`FrameClock::step`]
+    /// is called.
+    Paused,
+}
+
+pub struct FrameClock {
+    mode: ClockMode,
+    /// Mode to go back to when unpausing.
+    resume_mode: ClockMode,
+    pending_steps: u32,
+    last_instant: Instant,
+
+    dt: f32,
+    real_dt: f32,
+    elapsed: f32,
+    frame: u64,
+}
+
+impl FrameClock {
+    pub fn new(mode: ClockMode) -> Self {
+        let resume_mode = match mode {
+            ClockMode::Paused => ClockMode::RealTime,
+            mode => mode,
+        };
+
+        Self {
+            mode,
+            resume_mode,
+            pending_steps: 0,
+            last_instant: Instant::now(),
+            dt: 0.0,
+            real_dt: 0.0,
+            elapsed: 0.0,
+            frame: 0,
+        }
+    }
+
+    pub fn mode(&self) -> ClockMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: ClockMode) {
+        if mode != ClockMode::Paused {
+            self.resume_mode = mode;
+        }
+
+        self.mode = mode;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.mode == ClockMode::Paused
+    }
+
+    /// Pauses the clock, or resumes it in the mode it was in before.
+    pub fn toggle_pause(&mut self) {
+        if self.is_paused() {
+            self.mode = self.resume_mode;
+        } else {
+            self.mode = ClockMode::Paused;
+        }
+    }
+
+    /// Pauses the clock if needed, and advances the next frame by
+    /// [`SINGLE_STEP`].
+    pub fn step(&mut self) {
+        self.mode = ClockMode::Paused;
+        self.pending_steps += 1;
+    }
+
+    /// Advances the clock to the next frame.
+    pub fn tick(&mut self) {
+        let now = Instant::now();
+        self.real_dt = (now - self.last_instant).as_secs_f32();
+        self.last_instant = now;
+
+        self.dt = match self.mode {
+            ClockMode::RealTime => self.real_dt,
+            ClockMode::FixedStep(step) => step,
+            ClockMode::Paused if self.pending_steps > 0 => {
+                self.pending_steps -= 1;
+                SINGLE_STEP
+            }
+            ClockMode::Paused => 0.0,
+        };
+
+        self.elapsed += self.dt;
+        self.frame += 1;
+    }
+
+    /// Simulated time between the previous frame and this one, in seconds.
+    pub fn dt(&self) -> f32 {
+        self.dt
+    }
+
+    /// Real time between the previous frame and this one, in seconds.
+    pub fn real_dt(&self) -> f32 {
+        self.real_dt
+    }
+
+    /// Time step for interactions that should keep working while paused, like
+    /// moving the camera: the simulated one, or the real one while paused.
+    pub fn interaction_dt(&self) -> f32 {
+        match self.mode {
+            ClockMode::Paused => self.real_dt,
+            _ => self.dt,
+        }
+    }
+
+    /// Simulated time since the clock was created, in seconds.
+    pub fn elapsed(&self) -> f32 {
+        self.elapsed
+    }
+
+    /// Number of frames since the clock was created, paused ones included.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+}
+
+impl Default for FrameClock {
+    fn default() -> Self {
+        Self::new(ClockMode::RealTime)
+    }
+}
@@ -0,0 +1,233 @@
+//! Golden-image tests. Every scene is rendered headless with its default
+//! parameters and a fixed seed, then compared to a reference PNG in
+//! `tests/golden`. Scenes with other modes that must look the same are also
+//! rendered in those modes, against the same reference.
+//!
+//! Run `UPDATE_GOLDEN=1 cargo test` to rewrite the references after an intended
+//! change. When a comparison fails, the actual render and a diff image are
+//! written to `target/golden`.
+//!
+//! Tests fail when there is no headless GL context, unless `SKIP_GOLDEN=1` is
+//! set for machines that can't render at all.
+
+use std::{path::PathBuf, sync::Mutex};
+
+use glam::{uvec2, UVec2};
+use image::{Rgba, RgbaImage};
+
+use crate::{
+    headless::HeadlessContext,
+    scenes::{
+        round_quads::{QuadMode, QuadStyle},
+        SceneOptions,
+    },
+};
+
+const SIZE: UVec2 = uvec2(480, 270);
+const FRAMES: u32 = 3;
+const SEED: u64 = 0x5eed;
+
+/// Maximum perceptual distance between two pixels that are considered equal,
+/// from 0 to 1.
+const PIXEL_THRESHOLD: f32 = 0.05;
+
+/// Fraction of pixels that are allowed to differ, to absorb rasterization
+/// differences between drivers.
+const MAX_DIFF_RATIO: f32 = 0.001;
+
+/// Maximum value of [`color_delta`], between black and white.
+const MAX_DELTA: f32 = 35215.0;
+
+// GL function pointers are global, so only one test renders at a time.
+static GL_LOCK: Mutex<()> = Mutex::new(());
+
+#[test]
+fn round_quads() {
+    check_golden("Round Quads", "round-quads");
+}
+
+#[test]
+fn round_quads_instanced() {
+    // every mode draws the same quads, so they share the reference
+    let options = SceneOptions {
+        quad_mode: Some(QuadMode::Instanced),
+        ..Default::default()
+    };
+    check_golden_with("Round Quads", "round-quads", options);
+}
+
+#[test]
+fn round_quads_gpu() {
+    let options = SceneOptions {
+        quad_mode: Some(QuadMode::Gpu),
+        ..Default::default()
+    };
+    check_golden_with("Round Quads", "round-quads", options);
+}
+
+#[test]
+fn round_quads_stress_gpu() {
+    // the stress test starts from its smallest count, before the GPU buffers
+    // exist
+    let options = SceneOptions {
+        quad_mode: Some(QuadMode::Gpu),
+        stress_budget_ms: Some(16.0),
+        ..Default::default()
+    };
+    check_golden_with("Round Quads", "round-quads-stress", options);
+}
+
+#[test]
+fn round_quads_random_style() {
+    let options = SceneOptions {
+        quad_style: Some(QuadStyle::Random),
+        ..Default::default()
+    };
+    check_golden_with("Round Quads", "round-quads-random", options);
+}
+
+#[test]
+fn round_quads_random_style_gpu() {
+    // the packed records and the transform feedback must keep every field
+    let options = SceneOptions {
+        quad_mode: Some(QuadMode::Gpu),
+        quad_style: Some(QuadStyle::Random),
+        ..Default::default()
+    };
+    check_golden_with("Round Quads", "round-quads-random", options);
+}
+
+#[test]
+fn blurring() {
+    check_golden("Blurring", "blurring");
+}
+
+#[test]
+fn kawase() {
+    check_golden("Kawase", "kawase");
+}
+
+#[test]
+fn box_blur() {
+    check_golden("Box Blur", "box-blur");
+}
+
+#[test]
+fn sat_blur() {
+    check_golden("SAT Blur", "sat-blur");
+}
+
+fn check_golden(scene: &str, name: &str) {
+    check_golden_with(scene, name, SceneOptions::default());
+}
+
+/// Compares a render with other options than the default ones. The seed is
+/// always [`SEED`].
+fn check_golden_with(scene: &str, name: &str, options: SceneOptions) {
+    let actual = {
+        let _lock = GL_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+        let context = match HeadlessContext::new() {
+            Ok(context) => context,
+            // only on request, so that a broken driver can't pass every test
+            Err(e) if std::env::var_os("SKIP_GOLDEN").is_some() => {
+                eprintln!("skipping {name} golden test, no headless GL context: {e}");
+                return;
+            }
+            Err(e) => panic!("no headless GL context: {e}\nrun with SKIP_GOLDEN=1 to skip"),
+        };
+
+        let options = SceneOptions {
+            seed: Some(SEED),
+            ..options
+        };
+        context.render(scene, &options, SIZE, FRAMES).unwrap()
+    };
+
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let reference_path = manifest_dir.join(format!("tests/golden/{name}.png"));
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        actual.save(&reference_path).unwrap();
+        return;
+    }
+
+    let reference = match image::open(&reference_path) {
+        Ok(reference) => reference.into_rgba8(),
+        Err(e) => panic!(
+            "cannot open {}: {e}\nrun with UPDATE_GOLDEN=1 to create it",
+            reference_path.display()
+        ),
+    };
+
+    let (diff, diff_count) = compare(&reference, &actual);
+    let diff_ratio = diff_count as f32 / (actual.width() * actual.height()) as f32;
+
+    if reference.dimensions() != actual.dimensions() || diff_ratio > MAX_DIFF_RATIO {
+        let out_dir = manifest_dir.join("target/golden");
+        std::fs::create_dir_all(&out_dir).unwrap();
+
+        let actual_path = out_dir.join(format!("{name}.actual.png"));
+        let diff_path = out_dir.join(format!("{name}.diff.png"));
+        actual.save(&actual_path).unwrap();
+        diff.save(&diff_path).unwrap();
+
+        panic!(
+            "{scene} differs from {}: {diff_count} pixels ({:.3}%)\nactual: {}\ndiff:   {}",
+            reference_path.display(),
+            diff_ratio * 100.0,
+            actual_path.display(),
+            diff_path.display(),
+        );
+    }
+}
+
+/// Counts the pixels that differ, and makes a diff image where they are
+/// red over a faded copy of the reference.
+fn compare(reference: &RgbaImage, actual: &RgbaImage) -> (RgbaImage, u32) {
+    if reference.dimensions() != actual.dimensions() {
+        let (w, h) = actual.dimensions();
+        return (RgbaImage::from_pixel(w, h, Rgba([255, 0, 0, 255])), w * h);
+    }
+
+    let mut diff_count = 0;
+    let diff = RgbaImage::from_fn(actual.width(), actual.height(), |x, y| {
+        let a = *reference.get_pixel(x, y);
+        let b = *actual.get_pixel(x, y);
+
+        if color_delta(a, b) > MAX_DELTA * PIXEL_THRESHOLD * PIXEL_THRESHOLD {
+            diff_count += 1;
+            Rgba([255, 0, 0, 255])
+        } else {
+            let [luma, ..] = yiq(blend_white(a));
+            let faded = (255.0 - 0.1 * (255.0 - luma)) as u8;
+            Rgba([faded, faded, faded, 255])
+        }
+    });
+
+    (diff, diff_count)
+}
+
+// Perceptual color difference in the YIQ color space, as in
+// "Measuring perceived color difference using YIQ NTSC transmission color space
+// in mobile applications" (Kotsarenko, Ramos).
+fn color_delta(a: Rgba<u8>, b: Rgba<u8>) -> f32 {
+    let [ya, ia, qa] = yiq(blend_white(a));
+    let [yb, ib, qb] = yiq(blend_white(b));
+
+    let (y, i, q) = (ya - yb, ia - ib, qa - qb);
+    0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q
+}
+
+fn blend_white(Rgba([r, g, b, a]): Rgba<u8>) -> [f32; 3] {
+    let a = a as f32 / 255.0;
+    [r, g, b].map(|c| 255.0 + (c as f32 - 255.0) * a)
+}
+
+fn yiq([r, g, b]: [f32; 3]) -> [f32; 3] {
+    [
+        r * 0.298_895 + g * 0.586_622 + b * 0.114_482,
+        r * 0.595_978 - g * 0.274_176 - b * 0.321_802,
+        r * 0.211_470 - g * 0.522_617 + b * 0.311_147,
+    ]
+}
@@ -0,0 +1,299 @@
+//! GPU profiler timing the same scopes as the debug groups.
+//!
+//! Debug groups nest, but only one `GL_TIME_ELAPSED` query can be active at a
+//! time, so each scope is measured with a pair of `GL_TIMESTAMP` queries
+//! instead. Queries are read back a few frames later, once their results are
+//! available, so that profiling never stalls the pipeline.
+
+#![allow(clippy::missing_safety_doc)]
+
+use std::{cell::RefCell, collections::VecDeque, ffi::CStr, fmt::Write as _};
+
+use gl::types::{GLint, GLsizei, GLuint, GLuint64};
+
+/// Number of frames whose queries can be in flight at once.
+const FRAMES_IN_FLIGHT: usize = 4;
+
+/// Number of frames in the rolling average.
+const AVERAGE_WINDOW: usize = 60;
+
+thread_local! {
+    static PROFILER: RefCell<Option<GpuProfiler>> = const { RefCell::new(None) };
+}
+
+/// Rolling average of the GPU time spent in a scope per frame.
+#[derive(Debug, Clone)]
+pub struct ScopeTiming {
+    pub name: String,
+    pub depth: usize,
+    pub average_ms: f32,
+    /// How many times the scope ran per frame, e.g. one per blur pass.
+    pub count: u32,
+}
+
+pub fn is_enabled() -> bool {
+    PROFILER.with_borrow(|profiler| profiler.is_some())
+}
+
+/// Starts or stops profiling. Stopping deletes all queries and timings.
+pub unsafe fn set_enabled(enabled: bool) {
+    PROFILER.with_borrow_mut(|profiler| match (enabled, profiler.take()) {
+        (true, None) => *profiler = Some(GpuProfiler::new()),
+        (true, Some(running)) => *profiler = Some(running),
+        (false, Some(running)) => running.delete(),
+        (false, None) => (),
+    });
+}
+
+/// Marks the start of a frame, and collects the timings of the oldest frame
+/// still in flight if they are available.
+pub unsafe fn begin_frame() {
+    PROFILER.with_borrow_mut(|profiler| {
+        if let Some(profiler) = profiler {
+            profiler.begin_frame();
+        }
+    });
+}
+
+pub unsafe fn end_frame() {
+    PROFILER.with_borrow_mut(|profiler| {
+        if let Some(profiler) = profiler {
+            profiler.end_scope();
+        }
+    });
+}
+
+pub(crate) unsafe fn begin_scope(name: &CStr) {
+    PROFILER.with_borrow_mut(|profiler| {
+        if let Some(profiler) = profiler {
+            profiler.begin_scope(&name.to_string_lossy());
+        }
+    });
+}
+
+pub(crate) unsafe fn end_scope() {
+    PROFILER.with_borrow_mut(|profiler| {
+        if let Some(profiler) = profiler {
+            profiler.end_scope();
+        }
+    });
+}
+
+/// Current timings, in the order the scopes first ran.
+pub fn timings() -> Vec<ScopeTiming> {
+    PROFILER.with_borrow(|profiler| match profiler {
+        Some(profiler) => profiler.timings(),
+        None => Vec::new(),
+    })
+}
+
+/// Table of the current timings, one scope per line.
+pub fn report() -> String {
+    let mut report = String::new();
+
+    for timing in timings() {
+        let indent = "  ".repeat(timing.depth);
+        let name = format!("{indent}{}", timing.name);
+        let _ = write!(report, "{name:<40} {:>8.3} ms", timing.average_ms);
+
+        if timing.count > 1 {
+            let _ = write!(report, " ({}x)", timing.count);
+        }
+
+        report.push('\n');
+    }
+
+    report
+}
+
+struct GpuProfiler {
+    frames: [FrameQueries; FRAMES_IN_FLIGHT],
+    current: usize,
+    /// Indices of the open scopes in the current frame.
+    stack: Vec<usize>,
+    stats: Vec<ScopeStats>,
+    collected_frames: u64,
+}
+
+#[derive(Default)]
+struct FrameQueries {
+    scopes: Vec<ScopeQueries>,
+    used: usize,
+}
+
+struct ScopeQueries {
+    name: String,
+    /// Names of the scope and its parents, to tell apart scopes with the same
+    /// name in different places.
+    path: String,
+    depth: usize,
+    start: GLuint,
+    end: GLuint,
+}
+
+struct ScopeStats {
+    name: String,
+    path: String,
+    depth: usize,
+    /// Total time and number of runs for each of the last frames.
+    samples: VecDeque<(f32, u32)>,
+    last_seen: u64,
+    /// Position of the scope's first run in the last frame it ran.
+    order: usize,
+}
+
+impl GpuProfiler {
+    fn new() -> Self {
+        Self {
+            frames: Default::default(),
+            current: 0,
+            stack: Vec::new(),
+            stats: Vec::new(),
+            collected_frames: 0,
+        }
+    }
+
+    unsafe fn delete(self) {
+        for frame in &self.frames {
+            for scope in &frame.scopes {
+                let queries = [scope.start, scope.end];
+                gl::DeleteQueries(queries.len() as GLsizei, queries.as_ptr());
+            }
+        }
+    }
+
+    unsafe fn begin_frame(&mut self) {
+        self.current = (self.current + 1) % FRAMES_IN_FLIGHT;
+        self.collect();
+
+        self.stack.clear();
+        self.frames[self.current].used = 0;
+        self.begin_scope("Frame");
+    }
+
+    unsafe fn begin_scope(&mut self, name: &str) {
+        let (path, depth) = match self.stack.last() {
+            Some(&parent) => {
+                let parent = &self.frames[self.current].scopes[parent];
+                (format!("{}\0{name}", parent.path), parent.depth + 1)
+            }
+            None => (name.to_string(), 0),
+        };
+
+        let frame = &mut self.frames[self.current];
+        if frame.used == frame.scopes.len() {
+            let mut queries = [0; 2];
+            gl::GenQueries(queries.len() as GLsizei, queries.as_mut_ptr());
+
+            frame.scopes.push(ScopeQueries {
+                name: String::new(),
+                path: String::new(),
+                depth: 0,
+                start: queries[0],
+                end: queries[1],
+            });
+        }
+
+        let index = frame.used;
+        let scope = &mut frame.scopes[index];
+        scope.name = name.to_string();
+        scope.path = path;
+        scope.depth = depth;
+        gl::QueryCounter(scope.start, gl::TIMESTAMP);
+
+        frame.used += 1;
+        self.stack.push(index);
+    }
+
+    unsafe fn end_scope(&mut self) {
+        if let Some(index) = self.stack.pop() {
+            let scope = &self.frames[self.current].scopes[index];
+            gl::QueryCounter(scope.end, gl::TIMESTAMP);
+        }
+    }
+
+    /// Reads back the queries of the current slot, issued `FRAMES_IN_FLIGHT`
+    /// frames ago. They are dropped if the GPU still hasn't finished them.
+    unsafe fn collect(&mut self) {
+        let frame = &self.frames[self.current];
+        // the frame scope is the first one pushed and the last one ended, so
+        // the others are available once it is
+        let Some(frame_scope) = frame.scopes[..frame.used].first() else {
+            return;
+        };
+
+        let mut available: GLint = 0;
+        gl::GetQueryObjectiv(frame_scope.end, gl::QUERY_RESULT_AVAILABLE, &mut available);
+        if available == 0 {
+            return;
+        }
+
+        self.collected_frames += 1;
+
+        // accumulate scopes that ran several times this frame
+        let mut frame_totals: Vec<(usize, f32, u32)> = Vec::new();
+        for (order, scope) in frame.scopes[..frame.used].iter().enumerate() {
+            let (mut start, mut end): (GLuint64, GLuint64) = (0, 0);
+            gl::GetQueryObjectui64v(scope.start, gl::QUERY_RESULT, &mut start);
+            gl::GetQueryObjectui64v(scope.end, gl::QUERY_RESULT, &mut end);
+            let ms = end.saturating_sub(start) as f32 / 1_000_000.0;
+
+            let stat = match self.stats.iter().position(|stat| stat.path == scope.path) {
+                Some(stat) => stat,
+                None => {
+                    self.stats.push(ScopeStats {
+                        name: scope.name.clone(),
+                        path: scope.path.clone(),
+                        depth: scope.depth,
+                        samples: VecDeque::with_capacity(AVERAGE_WINDOW),
+                        last_seen: 0,
+                        order,
+                    });
+                    self.stats.len() - 1
+                }
+            };
+
+            match frame_totals.iter_mut().find(|(i, ..)| *i == stat) {
+                Some((_, total, count)) => {
+                    *total += ms;
+                    *count += 1;
+                }
+                None => {
+                    frame_totals.push((stat, ms, 1));
+                    self.stats[stat].order = order;
+                }
+            }
+        }
+
+        for (stat, total, count) in frame_totals {
+            let stat = &mut self.stats[stat];
+            if stat.samples.len() == AVERAGE_WINDOW {
+                stat.samples.pop_front();
+            }
+            stat.samples.push_back((total, count));
+            stat.last_seen = self.collected_frames;
+        }
+
+        // forget scopes that stopped running, e.g. after a scene switch
+        let collected_frames = self.collected_frames;
+        (self.stats).retain(|stat| collected_frames - stat.last_seen < AVERAGE_WINDOW as u64);
+        (self.stats).sort_by_key(|stat| (stat.last_seen != collected_frames, stat.order));
+    }
+
+    fn timings(&self) -> Vec<ScopeTiming> {
+        (self.stats.iter())
+            .map(|stat| {
+                let n = stat.samples.len().max(1) as f32;
+                let total_ms = stat.samples.iter().map(|(ms, _)| ms).sum::<f32>();
+                let (_, count) = stat.samples.back().copied().unwrap_or_default();
+
+                ScopeTiming {
+                    name: stat.name.clone(),
+                    depth: stat.depth,
+                    average_ms: total_ms / n,
+                    count,
+                }
+            })
+            .collect()
+    }
+}
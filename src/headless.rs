@@ -0,0 +1,136 @@
+//! Offscreen rendering without a window, for machines that have neither a
+//! display nor a GPU (Mesa's llvmpipe is enough).
+
+use std::{error::Error, ffi::CString};
+
+use glam::{vec2, UVec2, Vec2};
+use glutin::{
+    api::egl::{context::PossiblyCurrentContext, device::Device, display::Display},
+    config::{ConfigSurfaceTypes, ConfigTemplateBuilder},
+    context::{ContextApi, ContextAttributesBuilder, Version},
+    display::GlDisplay as _,
+};
+use image::RgbaImage;
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    common_gl::{read_framebuffer, set_screen_framebuffer, ColorFormat, FramebufferDesc},
+    frame_clock::{ClockMode, FrameClock},
+    scene_controller::SceneController,
+    scenes::{default_registry, SceneOptions, Scenes},
+};
+
+pub const DEFAULT_SIZE: UVec2 = UVec2::new(800, 600);
+
+/// Simulated time between two rendered frames, in seconds.
+const FRAME_STEP: f32 = 1.0 / 60.0;
+
+/// Far away from anything, so that scenes reacting to the mouse stay still.
+const NO_MOUSE_POS: Vec2 = vec2(-1.0e6, -1.0e6);
+
+/// Surfaceless EGL context, current on the thread that created it.
+pub struct HeadlessContext {
+    _gl_context: PossiblyCurrentContext,
+    _gl_display: Display,
+}
+
+impl HeadlessContext {
+    pub fn new() -> Result<Self, Box<dyn Error>> {
+        let device = (Device::query_devices()?)
+            .next()
+            .ok_or("no EGL device found")?;
+
+        let gl_display = unsafe { Display::with_device(&device, None)? };
+
+        // No surface at all, we only ever draw to framebuffer objects.
+        let template = ConfigTemplateBuilder::new()
+            .with_alpha_size(8)
+            .with_surface_type(ConfigSurfaceTypes::empty())
+            .build();
+
+        let gl_config = (unsafe { gl_display.find_configs(template)? })
+            .next()
+            .ok_or("no EGL config found")?;
+
+        let context_attributes = ContextAttributesBuilder::new()
+            .with_context_api(ContextApi::OpenGl(Some(Version::new(3, 3))))
+            .build(None);
+
+        let fallback_context_attributes = ContextAttributesBuilder::new()
+            .with_context_api(ContextApi::Gles(None))
+            .build(None);
+
+        let gl_context = unsafe {
+            gl_display
+                .create_context(&gl_config, &context_attributes)
+                .or_else(|_| gl_display.create_context(&gl_config, &fallback_context_attributes))?
+        }
+        .make_current_surfaceless()?;
+
+        gl::load_with(|symbol| {
+            let symbol = CString::new(symbol).unwrap();
+            gl_display.get_proc_address(symbol.as_c_str()).cast()
+        });
+
+        Ok(Self {
+            _gl_context: gl_context,
+            _gl_display: gl_display,
+        })
+    }
+
+    /// Renders `frames` frames of a scene, and reads back the last one.
+    pub fn render(
+        &self,
+        scene: &str,
+        options: &SceneOptions,
+        size: UVec2,
+        frames: u32,
+    ) -> Result<RgbaImage, Box<dyn Error>> {
+        let (image, _) = self.render_with_format(scene, options, size, frames)?;
+        Ok(image)
+    }
+
+    /// Same as [`Self::render`], also returning the format the scene ended up
+    /// rendering its framebuffers in.
+    pub fn render_with_format(
+        &self,
+        scene: &str,
+        options: &SceneOptions,
+        size: UVec2,
+        frames: u32,
+    ) -> Result<(RgbaImage, Option<ColorFormat>), Box<dyn Error>> {
+        let registry = default_registry();
+        let index = registry.find(scene)?;
+
+        unsafe {
+            let win_size = PhysicalSize::new(size.x, size.y);
+            let mut scenes = Scenes::new(win_size, registry, options.clone(), index);
+
+            if let Some(e) = scenes.error() {
+                return Err(e.clone().into());
+            }
+
+            let target = FramebufferDesc::new("headless", size).create()?;
+            set_screen_framebuffer(target.fbo);
+
+            let mut scene_ctrl = SceneController::new(1.0, 0.5);
+            let mut clock = FrameClock::new(ClockMode::FixedStep(FRAME_STEP));
+
+            for _ in 0..frames.max(1) {
+                clock.tick();
+                scene_ctrl.update(&clock);
+                scenes.resize(&scene_ctrl.camera, size.x as i32, size.y as i32);
+                scenes.draw(&scene_ctrl.camera, &clock, NO_MOUSE_POS);
+            }
+
+            gl::Finish();
+            let image = read_framebuffer(&target);
+            let format = scenes.current_color_format();
+
+            drop(scenes);
+            set_screen_framebuffer(0);
+
+            Ok((image, format))
+        }
+    }
+}
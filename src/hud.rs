@@ -0,0 +1,323 @@
+//! On-screen overlay showing the current scene, its parameters and how long
+//! frames take, drawn on top of the scene with a built-in bitmap font.
+
+mod font;
+
+use std::{collections::VecDeque, mem};
+
+use gl::types::{GLint, GLsizei, GLsizeiptr};
+use glam::{uvec2, vec2, vec4, UVec2, Vec2, Vec4};
+
+use crate::{
+    common_gl::{
+        bind_screen_framebuffer, upload_texture, vertex_layout, Buffer, ShaderError, ShaderProgram,
+        Texture, VertexArray,
+    },
+    frame_clock::FrameClock,
+    gpu_profiler,
+    shaders::{build_program, shader_source, ShaderSource},
+};
+
+use font::{FIRST_CHAR, GLYPHS, GLYPH_HEIGHT, GLYPH_WIDTH};
+
+const SRC_VERT_HUD: ShaderSource = shader_source!("hud.vert");
+const SRC_FRAG_HUD: ShaderSource = shader_source!("hud.frag");
+
+/// Glyphs are laid out in a 16x6 grid in the font texture. The last cell, where
+/// DEL would be, is filled to draw solid rectangles.
+const ATLAS_COLUMNS: u32 = 16;
+const ATLAS_ROWS: u32 = 6;
+const SOLID_CELL: u32 = ATLAS_COLUMNS * ATLAS_ROWS - 1;
+
+/// Number of frames in the frame time graph and the FPS average.
+const HISTORY_LEN: usize = 120;
+
+/// Frame time at the top of the graph, in milliseconds.
+const GRAPH_MAX_MS: f32 = 50.0;
+const GRAPH_HEIGHT: f32 = 25.0;
+const TARGET_MS: f32 = 1000.0 / 60.0;
+
+const MARGIN: f32 = 4.0;
+
+const TEXT_COLOR: Vec4 = vec4(1.0, 1.0, 1.0, 1.0);
+const DIM_TEXT_COLOR: Vec4 = vec4(0.7, 0.7, 0.7, 1.0);
+const PANEL_COLOR: Vec4 = vec4(0.0, 0.0, 0.0, 0.6);
+const GOOD_COLOR: Vec4 = vec4(0.3, 0.9, 0.4, 1.0);
+const SLOW_COLOR: Vec4 = vec4(0.95, 0.8, 0.2, 1.0);
+const BAD_COLOR: Vec4 = vec4(0.95, 0.3, 0.25, 1.0);
+
+pub struct Hud {
+    pub visible: bool,
+    /// Size of a font pixel in screen pixels.
+    scale: f32,
+
+    shader: ShaderProgram,
+    vao: VertexArray,
+    vbo: Buffer,
+    font_texture: Texture,
+
+    /// Error shown until it's cleared, like a shader that failed to compile.
+    error: Option<String>,
+
+    vertices: Vec<Vertex>,
+    /// Real (not simulated) frame times in milliseconds, oldest first.
+    frame_times: VecDeque<f32>,
+}
+
+impl Hud {
+    pub fn new(scale_factor: f32) -> Result<Self, ShaderError> {
+        unsafe {
+            let shader = build_program(&SRC_VERT_HUD, &SRC_FRAG_HUD)?;
+
+            let vao = VertexArray::new();
+            gl::BindVertexArray(vao.id());
+
+            let vbo = Buffer::new();
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo.id());
+
+            shader.bind_vertex_layout::<Vertex>();
+
+            let atlas = font_atlas();
+            let font_texture = Texture::new();
+            upload_texture(
+                font_texture.id(),
+                atlas.width(),
+                atlas.height(),
+                atlas.as_ptr(),
+                gl::CLAMP_TO_EDGE,
+            );
+
+            // keep the pixel font crisp
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
+
+            Ok(Self {
+                visible: true,
+                scale: 2.0 * scale_factor.round().max(1.0),
+
+                shader,
+                vao,
+                vbo,
+                font_texture,
+
+                error: None,
+
+                vertices: Vec::new(),
+                frame_times: VecDeque::with_capacity(HISTORY_LEN),
+            })
+        }
+    }
+
+    pub fn set_error(&mut self, error: Option<String>) {
+        self.error = error;
+    }
+
+    /// Adds the last frame to the frame time graph.
+    pub fn record_frame(&mut self, clock: &FrameClock) {
+        if self.frame_times.len() == HISTORY_LEN {
+            self.frame_times.pop_front();
+        }
+
+        self.frame_times.push_back(clock.real_dt() * 1000.0);
+    }
+
+    pub fn draw(&mut self, viewport: UVec2, scene_name: &str, params: &str, clock: &FrameClock) {
+        if !self.visible || viewport.x == 0 || viewport.y == 0 {
+            return;
+        }
+
+        self.vertices.clear();
+
+        let avg_ms = match self.frame_times.len() {
+            0 => 0.0,
+            n => self.frame_times.iter().sum::<f32>() / n as f32,
+        };
+        let fps = if avg_ms > 0.0 { 1000.0 / avg_ms } else { 0.0 };
+
+        let mut lines = vec![
+            (scene_name.to_string(), TEXT_COLOR),
+            (format!("{fps:.1} fps  {avg_ms:.2} ms"), TEXT_COLOR),
+        ];
+
+        if !params.is_empty() {
+            lines.push((params.to_string(), DIM_TEXT_COLOR));
+        }
+
+        if clock.is_paused() {
+            lines.push(("paused".to_string(), SLOW_COLOR));
+        }
+
+        let gpu_lines = (gpu_profiler::timings().into_iter()).map(|timing| {
+            let indent = " ".repeat(timing.depth);
+            let text = format!("{indent}{} {:.3} ms", timing.name, timing.average_ms);
+            (text, DIM_TEXT_COLOR)
+        });
+
+        let error_lines = (self.error.iter())
+            .flat_map(|error| error.lines())
+            .map(|line| (line.to_string(), BAD_COLOR));
+
+        let gpu_lines = gpu_lines.chain(error_lines).collect::<Vec<_>>();
+
+        // panel behind everything
+        let line_height = (GLYPH_HEIGHT as f32 + 2.0) * self.scale;
+        let graph_size = vec2(HISTORY_LEN as f32, GRAPH_HEIGHT) * self.scale;
+
+        let text_width = (lines.iter().chain(&gpu_lines))
+            .map(|(text, _)| text.chars().count() as f32 * GLYPH_WIDTH as f32 * self.scale)
+            .fold(graph_size.x, f32::max);
+
+        let line_count = (lines.len() + gpu_lines.len()) as f32;
+        let margin = MARGIN * self.scale;
+        let panel_size = vec2(
+            text_width + 2.0 * margin,
+            line_count * line_height + graph_size.y + 3.0 * margin,
+        );
+
+        self.push_rect(Vec2::ZERO, panel_size, PANEL_COLOR);
+
+        let mut pos = Vec2::splat(margin);
+        for (text, color) in &lines {
+            self.push_text(pos, text, *color);
+            pos.y += line_height;
+        }
+
+        self.push_graph(pos, graph_size);
+        pos.y += graph_size.y + margin;
+
+        for (text, color) in &gpu_lines {
+            self.push_text(pos, text, *color);
+            pos.y += line_height;
+        }
+
+        unsafe {
+            bind_screen_framebuffer();
+            gl::Viewport(0, 0, viewport.x as i32, viewport.y as i32);
+
+            gl::Enable(gl::BLEND);
+            gl::BlendEquation(gl::FUNC_ADD);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+
+            self.shader.bind();
+            self.shader.set("u_screen_size", viewport.as_vec2());
+
+            gl::BindVertexArray(self.vao.id());
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo.id());
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                mem::size_of_val(self.vertices.as_slice()) as GLsizeiptr,
+                self.vertices.as_ptr() as *const _,
+                gl::STREAM_DRAW,
+            );
+
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, self.font_texture.id());
+            gl::DrawArrays(gl::TRIANGLES, 0, self.vertices.len() as GLsizei);
+        }
+    }
+
+    /// Bars of the last frame times, with a line at 60 FPS.
+    fn push_graph(&mut self, pos: Vec2, size: Vec2) {
+        let bar_width = size.x / HISTORY_LEN as f32;
+
+        for i in 0..self.frame_times.len() {
+            let ms = self.frame_times[i];
+            let height = (ms / GRAPH_MAX_MS).min(1.0) * size.y;
+
+            let color = if ms <= TARGET_MS * 1.1 {
+                GOOD_COLOR
+            } else if ms <= TARGET_MS * 2.0 {
+                SLOW_COLOR
+            } else {
+                BAD_COLOR
+            };
+
+            let bar_pos = pos + vec2(i as f32 * bar_width, size.y - height);
+            self.push_rect(bar_pos, vec2(bar_width, height), color);
+        }
+
+        let target_y = pos.y + size.y * (1.0 - TARGET_MS / GRAPH_MAX_MS);
+        let line_color = TEXT_COLOR.with_w(0.4);
+        self.push_rect(vec2(pos.x, target_y), vec2(size.x, self.scale), line_color);
+    }
+
+    fn push_text(&mut self, mut pos: Vec2, text: &str, color: Vec4) {
+        let glyph_size = uvec2(GLYPH_WIDTH, GLYPH_HEIGHT).as_vec2() * self.scale;
+
+        for ch in text.chars() {
+            let cell = match (ch as u32).checked_sub(FIRST_CHAR as u32) {
+                Some(cell) if (cell as usize) < GLYPHS.len() => cell,
+                _ => ('?' as u32) - (FIRST_CHAR as u32),
+            };
+
+            if ch != ' ' {
+                self.push_quad(pos, glyph_size, cell, color);
+            }
+
+            pos.x += glyph_size.x;
+        }
+    }
+
+    fn push_rect(&mut self, pos: Vec2, size: Vec2, color: Vec4) {
+        self.push_quad(pos, size, SOLID_CELL, color);
+    }
+
+    fn push_quad(&mut self, pos: Vec2, size: Vec2, cell: u32, color: Vec4) {
+        let atlas_size = uvec2(ATLAS_COLUMNS * GLYPH_WIDTH, ATLAS_ROWS * GLYPH_HEIGHT).as_vec2();
+        let cell_pos = uvec2(
+            cell % ATLAS_COLUMNS * GLYPH_WIDTH,
+            cell / ATLAS_COLUMNS * GLYPH_HEIGHT,
+        );
+
+        let uv_min = cell_pos.as_vec2() / atlas_size;
+        let uv_max = (cell_pos + uvec2(GLYPH_WIDTH, GLYPH_HEIGHT)).as_vec2() / atlas_size;
+
+        #[rustfmt::skip]
+        let corners = [
+            Vertex::new(pos,                      uv_min,                     color),
+            Vertex::new(pos + vec2(0.0, size.y),  vec2(uv_min.x, uv_max.y),   color),
+            Vertex::new(pos + size,               uv_max,                     color),
+            Vertex::new(pos + vec2(size.x, 0.0),  vec2(uv_max.x, uv_min.y),   color),
+        ];
+
+        let [a, b, c, d] = corners;
+        self.vertices.extend([a, b, c, a, c, d]);
+    }
+}
+
+/// White glyphs on a transparent background, with the solid cell filled.
+fn font_atlas() -> image::RgbaImage {
+    let width = ATLAS_COLUMNS * GLYPH_WIDTH;
+    let height = ATLAS_ROWS * GLYPH_HEIGHT;
+
+    image::RgbaImage::from_fn(width, height, |x, y| {
+        let cell = (y / GLYPH_HEIGHT) * ATLAS_COLUMNS + x / GLYPH_WIDTH;
+        let (gx, gy) = (x % GLYPH_WIDTH, y % GLYPH_HEIGHT);
+
+        let lit = match GLYPHS.get(cell as usize) {
+            Some(glyph) => glyph[gy as usize] & (0x80 >> gx) != 0,
+            None => cell == SOLID_CELL,
+        };
+
+        image::Rgba([255, 255, 255, if lit { 255 } else { 0 }])
+    })
+}
+
+vertex_layout! {
+    #[derive(Debug, Clone, Copy, Default)]
+    struct Vertex {
+        position: Vec2,
+        uv: Vec2,
+        color: Vec4,
+    }
+}
+
+impl Vertex {
+    const fn new(position: Vec2, uv: Vec2, color: Vec4) -> Self {
+        Self {
+            position,
+            uv,
+            color,
+        }
+    }
+}
@@ -6,6 +6,10 @@ use std::{
     sync::atomic::Ordering,
 };
 
+use banding::BandingReport;
+use capture::Recording;
+use cli::Args;
+use frame_clock::{ClockMode, FrameClock};
 use gl::types::{GLchar, GLenum, GLsizei, GLuint};
 use glam::{IVec2, Vec2};
 use glutin::{
@@ -18,10 +22,14 @@ use glutin::{
     surface::{GlSurface as _, Surface, SwapInterval, WindowSurface},
 };
 use glutin_winit::{DisplayBuilder, GlWindow as _};
+use headless::HeadlessContext;
+use hud::Hud;
 use scene_controller::SceneController;
-use scenes::Scenes;
+use scenes::{default_registry, InputImage, SceneOptions, Scenes};
+use shaders::ShaderWatcher;
 use winit::{
     application::ApplicationHandler,
+    dpi::PhysicalSize,
     event::{ElementState, KeyEvent, WindowEvent},
     event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
     keyboard::{Key, NamedKey},
@@ -29,22 +37,90 @@ use winit::{
     window::{Theme, Window, WindowAttributes},
 };
 
+pub mod banding;
 pub mod camera;
+pub mod capture;
+pub mod cli;
 pub mod common_gl;
+pub mod frame_clock;
+pub mod gpu_profiler;
+pub mod headless;
+pub mod hud;
 pub mod scene_controller;
 pub mod scenes;
+pub mod shaders;
+
+#[cfg(test)]
+mod golden_tests;
 
 fn main() {
+    let args = Args::parse();
+
+    let registry = default_registry();
+    let initial_scene = match &args.scene {
+        Some(name) => registry.find(name).unwrap_or_else(|e| {
+            eprintln!("Error: {e}");
+            std::process::exit(2);
+        }),
+        None => registry.index_of("Kawase").unwrap_or_default(),
+    };
+
+    if args.measure_banding {
+        let scene = registry.entries()[initial_scene].name();
+        let size = args.size.unwrap_or(headless::DEFAULT_SIZE);
+
+        let result = HeadlessContext::new().and_then(|context| {
+            BandingReport::measure(&context, scene, &args.scene_options, size, args.frames)
+        });
+
+        match result {
+            Ok(report) => print!("{report}"),
+            Err(e) => {
+                eprintln!("Error: {e}");
+                std::process::exit(1);
+            }
+        }
+
+        return;
+    }
+
+    if let Some(output) = &args.headless {
+        let scene = registry.entries()[initial_scene].name();
+        let size = args.size.unwrap_or(headless::DEFAULT_SIZE);
+
+        let result = HeadlessContext::new()
+            .and_then(|context| context.render(scene, &args.scene_options, size, args.frames))
+            .and_then(|image| Ok(image.save(output)?));
+
+        match result {
+            Ok(()) => println!("Rendered {scene} to {}", output.display()),
+            Err(e) => {
+                eprintln!("Error: {e}");
+                std::process::exit(1);
+            }
+        }
+
+        return;
+    }
+
+    // load shaders from `assets/shaders` and reload them when they change
+    shaders::enable_hot_reload();
+
     let event_loop = EventLoop::new().unwrap();
     event_loop.set_control_flow(ControlFlow::Poll);
 
-    let mut app = App::new(
-        WindowAttributes::default()
-            .with_active(true)
-            .with_theme(Some(Theme::Dark))
-            .with_title("OpenGL Playground")
-            .with_resizable(true),
-    );
+    let mut win_attribs = WindowAttributes::default()
+        .with_active(true)
+        .with_theme(Some(Theme::Dark))
+        .with_title("OpenGL Playground")
+        .with_resizable(true);
+
+    if let Some(size) = args.size {
+        win_attribs = win_attribs.with_inner_size(PhysicalSize::new(size.x, size.y));
+    }
+
+    let mut app = App::new(win_attribs, initial_scene, args.scene_options, args.vsync);
+    app.clock.set_mode(args.clock_mode);
 
     event_loop.run_app(&mut app).unwrap();
 }
@@ -61,14 +137,29 @@ struct App {
     display_builder: DisplayBuilder,
     not_current_gl_context: Option<NotCurrentContext>,
     scenes: Option<(Scenes, SceneController)>,
+    hud: Option<Hud>,
+    shader_watcher: ShaderWatcher,
+    initial_scene: usize,
+    scene_options: SceneOptions,
+    vsync: bool,
+    screenshot_requested: bool,
     state: Option<AppState>,
 
+    clock: FrameClock,
+    /// Ongoing recording, and the clock mode to go back to when it stops.
+    recording: Option<(Recording, ClockMode)>,
+
     viewport: IVec2,
     mouse_pos: Vec2,
 }
 
 impl App {
-    fn new(win_attribs: WindowAttributes) -> Self {
+    fn new(
+        win_attribs: WindowAttributes,
+        initial_scene: usize,
+        scene_options: SceneOptions,
+        vsync: bool,
+    ) -> Self {
         // The template will match only the configurations supporting rendering
         // to windows.
         //
@@ -90,14 +181,71 @@ impl App {
             display_builder,
             not_current_gl_context: None,
             scenes: None,
+            hud: None,
+            shader_watcher: ShaderWatcher::new(),
+            initial_scene,
+            scene_options: SceneOptions {
+                vsync,
+                ..scene_options
+            },
+            vsync,
+            screenshot_requested: false,
             state: None,
 
+            clock: FrameClock::default(),
+            recording: None,
+
             viewport: IVec2::default(),
             mouse_pos: Vec2::default(),
         }
     }
 }
 
+impl App {
+    fn toggle_recording(&mut self) {
+        if self.recording.is_some() {
+            self.stop_recording();
+        } else {
+            self.start_recording();
+        }
+    }
+
+    fn start_recording(&mut self) {
+        let Some((scenes, _)) = &self.scenes else {
+            return;
+        };
+
+        let size = self.viewport.as_uvec2();
+        match Recording::start(scenes.current_name(), &scenes.current_params(), size) {
+            Ok(recording) => {
+                println!("recording started ({}x{})", size.x, size.y);
+
+                // every recorded frame advances the animations by the same amount
+                let prev_mode = self.clock.mode();
+                let step = 1.0 / capture::RECORDING_FPS as f32;
+                self.clock.set_mode(ClockMode::FixedStep(step));
+
+                self.recording = Some((recording, prev_mode));
+            }
+            Err(e) => eprintln!("Error starting recording: {e}"),
+        }
+    }
+
+    fn stop_recording(&mut self) {
+        let Some((recording, prev_mode)) = self.recording.take() else {
+            return;
+        };
+
+        self.clock.set_mode(prev_mode);
+
+        let frame_count = recording.frame_count();
+        match recording.finish() {
+            Ok(path) => println!("recorded {frame_count} frames to {}", path.display()),
+            Err(e) => eprintln!("Error finishing recording: {e}"),
+        }
+    }
+}
+
 impl ApplicationHandler for App {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         let (mut window, gl_config) = match self.display_builder.clone().build(
@@ -209,21 +357,43 @@ impl ApplicationHandler for App {
             }
         }
 
+        // Created before the scenes, so that its GL objects aren't taken for
+        // leaks of the first scene.
+        if self.hud.is_none() {
+            match Hud::new(window.scale_factor() as f32) {
+                Ok(hud) => self.hud = Some(hud),
+                Err(e) => eprintln!("Error creating HUD: {e}"),
+            }
+        }
+
         // The context needs to be current for the Renderer to set up shaders and
         // buffers.
         self.scenes.get_or_insert_with(|| {
-            let scenes = Scenes::new(window.as_ref());
+            let scenes = Scenes::new(
+                window.inner_size(),
+                default_registry(),
+                self.scene_options.clone(),
+                self.initial_scene,
+            );
             let scene_controller = SceneController::new(window.scale_factor() as f32, 0.5);
             (scenes, scene_controller)
         });
 
+        if let (Some(hud), Some((scenes, _))) = (&mut self.hud, &self.scenes) {
+            hud.set_error(scenes.error().map(ToString::to_string));
+        }
+
         let win_size = window.inner_size();
         self.viewport = IVec2::new(win_size.width as i32, win_size.height as i32);
 
         // Try setting vsync.
-        if let Err(res) = gl_surface
-            .set_swap_interval(&gl_context, SwapInterval::Wait(NonZeroU32::new(1).unwrap()))
-        {
+        let swap_interval = if self.vsync {
+            SwapInterval::Wait(NonZeroU32::new(1).unwrap())
+        } else {
+            SwapInterval::DontWait
+        };
+
+        if let Err(res) = gl_surface.set_swap_interval(&gl_context, swap_interval) {
             eprintln!("Error setting vsync: {res:?}");
         }
 
@@ -264,10 +434,26 @@ impl ApplicationHandler for App {
                 }
             }
 
+            WindowEvent::DroppedFile(ref path) => {
+                if let Some((scenes, _)) = &mut self.scenes {
+                    scenes.set_image(InputImage::File(path.clone()));
+                }
+            }
+
             WindowEvent::CursorMoved { position, .. } => {
                 self.mouse_pos = Vec2::new(position.x as f32, position.y as f32);
             }
 
+            WindowEvent::MouseInput { state, button, .. } => {
+                if let Some((scenes, scene_ctrl)) = &mut self.scenes {
+                    let camera = &scene_ctrl.camera;
+                    if scenes.on_mouse_button(camera, self.mouse_pos, button, state) {
+                        // the scene took the click, so the camera doesn't pan
+                        return;
+                    }
+                }
+            }
+
             WindowEvent::CloseRequested
             | WindowEvent::KeyboardInput {
                 event:
@@ -288,10 +474,41 @@ impl ApplicationHandler for App {
                     },
                 ..
             } => {
+                if let Key::Character(ch) = logical_key {
+                    if ch.eq_ignore_ascii_case("p") {
+                        self.screenshot_requested = true;
+                    } else if ch.eq_ignore_ascii_case("r") {
+                        self.toggle_recording();
+                    } else if ch.eq_ignore_ascii_case("i") {
+                        if let Some((scenes, _)) = &mut self.scenes {
+                            scenes.next_bundled_image();
+                        }
+                    } else if ch == "." {
+                        self.clock.step();
+                    } else if ch.eq_ignore_ascii_case("h") {
+                        if let Some(hud) = &mut self.hud {
+                            hud.visible = !hud.visible;
+                        }
+                    } else if ch.eq_ignore_ascii_case("g") {
+                        let enabled = !gpu_profiler::is_enabled();
+                        unsafe { gpu_profiler::set_enabled(enabled) };
+                        println!("gpu profiler: {}", if enabled { "on" } else { "off" });
+                    }
+                }
+
+                if let Key::Named(NamedKey::Space) = logical_key {
+                    self.clock.toggle_pause();
+                    println!("clock: {:?}", self.clock.mode());
+                }
+
                 if let Some(AppState { window, .. }) = self.state.as_ref() {
                     let (scenes, _) = self.scenes.as_mut().unwrap();
-                    scenes.switch_scene(window, logical_key.clone());
+                    scenes.switch_scene(window.inner_size(), logical_key.clone());
                     scenes.on_key(logical_key.clone());
+
+                    if let Some(hud) = &mut self.hud {
+                        hud.set_error(scenes.error().map(ToString::to_string));
+                    }
                 }
             }
 
@@ -303,7 +520,14 @@ impl ApplicationHandler for App {
         }
     }
 
+    fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
+        // ffmpeg only finalizes the video once its input is closed
+        self.stop_recording();
+    }
+
     fn about_to_wait(&mut self, _event_loop: &winit::event_loop::ActiveEventLoop) {
+        let mut recording_failed = false;
+
         if let Some(AppState {
             gl_context,
             gl_surface,
@@ -313,13 +537,77 @@ impl ApplicationHandler for App {
         {
             let (scenes, scene_ctrl) = self.scenes.as_mut().unwrap();
 
-            scene_ctrl.update();
+            if self.shader_watcher.poll() {
+                let result = scenes.reload_shaders(window.inner_size());
+
+                match &result {
+                    Ok(()) => println!("shaders reloaded"),
+                    Err(log) => eprintln!("Error reloading shaders, keeping the old ones:\n{log}"),
+                }
+
+                if let Some(hud) = &mut self.hud {
+                    hud.set_error(result.err().map(|e| e.to_string()));
+                }
+            }
+
+            self.clock.tick();
+            scene_ctrl.update(&self.clock);
             scenes.resize(&scene_ctrl.camera, self.viewport.x, self.viewport.y);
-            scenes.draw(&scene_ctrl.camera, self.mouse_pos);
+
+            unsafe { gpu_profiler::begin_frame() };
+            scenes.draw(&scene_ctrl.camera, &self.clock, self.mouse_pos);
+            unsafe { gpu_profiler::end_frame() };
+
+            if gpu_profiler::is_enabled() && self.clock.frame().is_multiple_of(60) {
+                println!(
+                    "gpu timings ({}):\n{}",
+                    scenes.current_name(),
+                    gpu_profiler::report()
+                );
+            }
+
+            if std::mem::take(&mut self.screenshot_requested) {
+                let image = unsafe { common_gl::read_screen_framebuffer(self.viewport.as_uvec2()) };
+                let result = capture::save_screenshot(
+                    &image,
+                    scenes.current_name(),
+                    &scenes.current_params(),
+                );
+
+                match result {
+                    Ok(path) => println!("screenshot: {}", path.display()),
+                    Err(e) => eprintln!("Error saving screenshot: {e}"),
+                }
+            }
+
+            if let Some((recording, _)) = &mut self.recording {
+                // at the window's size, so that a resize ends the recording
+                let image = unsafe { common_gl::read_screen_framebuffer(self.viewport.as_uvec2()) };
+
+                if let Err(e) = recording.push_frame(&image) {
+                    eprintln!("Error recording frame: {e}");
+                    recording_failed = true;
+                }
+            }
+
+            // after capturing, so that screenshots and recordings only show the scene
+            if let Some(hud) = &mut self.hud {
+                hud.record_frame(&self.clock);
+                hud.draw(
+                    self.viewport.as_uvec2(),
+                    scenes.current_name(),
+                    &scenes.current_params(),
+                    &self.clock,
+                );
+            }
 
             window.request_redraw();
             gl_surface.swap_buffers(gl_context).unwrap();
         }
+
+        if recording_failed {
+            self.stop_recording();
+        }
     }
 }
 
@@ -1,8 +1,6 @@
 //! A nice scene controller to smoothly move around in the window.
 
-use std::time::Instant;
-
-use crate::camera::Camera;
+use crate::{camera::Camera, frame_clock::FrameClock};
 
 use glam::{vec2, Vec2};
 use winit::event::{ElementState, MouseScrollDelta, WindowEvent};
@@ -19,11 +17,6 @@ pub struct SceneController {
     // for smooth scrolling
     pub scroll_speed: f32,
     hard_scale: Vec2,
-
-    // for FPS-independent interactions
-    start: Instant,
-    prev_elapsed: f32,
-    current_elapsed: f32,
 }
 
 impl SceneController {
@@ -43,15 +36,12 @@ impl SceneController {
             mouse_state: ElementState::Released,
             scroll_speed,
             hard_scale: scale,
-            start: Instant::now(),
-            prev_elapsed: 0.0,
-            current_elapsed: 0.0,
         }
     }
 
-    pub fn update(&mut self) {
+    pub fn update(&mut self, clock: &FrameClock) {
         // Smooth scrolling
-        let time_delta = self.current_elapsed - self.prev_elapsed;
+        let time_delta = clock.interaction_dt();
         self.camera.scale += time_delta.powf(0.6) * (self.hard_scale - self.camera.scale);
 
         // Mouse dragging
@@ -59,10 +49,6 @@ impl SceneController {
             self.camera.position =
                 self.camera_pos + (self.mouse_pos - self.mouse_pos_held) / self.camera.scale;
         }
-
-        // Frame interval
-        self.prev_elapsed = self.current_elapsed;
-        self.current_elapsed = self.start.elapsed().as_secs_f32();
     }
 
     pub fn interact(&mut self, event: &WindowEvent) {
@@ -89,12 +75,4 @@ impl SceneController {
             _ => (),
         }
     }
-
-    pub fn dt(&self) -> f32 {
-        self.current_elapsed - self.prev_elapsed
-    }
-
-    pub fn current_elapsed(&self) -> f32 {
-        self.current_elapsed
-    }
 }
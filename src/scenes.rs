@@ -1,72 +1,542 @@
-pub mod blurring;
-pub mod kawase;
-pub mod round_quads;
+pub mod blur_pipeline;
 
-use blurring::BlurringScene;
-use kawase::KawaseScene;
-use round_quads::RoundQuadsScene;
+/// Declares the modules of the scenes, each of which registers its scene with a
+/// `SCENE` constant, and collects them in F-key order.
+macro_rules! scene_modules {
+    ($($module:ident),* $(,)?) => {
+        $(pub mod $module;)*
+
+        const SCENES: &[SceneEntry] = &[$($module::SCENE),*];
+    };
+}
+
+scene_modules!(round_quads, blurring, kawase, box_blur, sat_blur);
+
+use round_quads::{QuadMode, QuadStyle};
+
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
 
 use glam::Vec2;
+use image::RgbaImage;
+use winit::dpi::PhysicalSize;
+use winit::event::{ElementState, MouseButton};
 use winit::keyboard::{Key, NamedKey, SmolStr};
-use winit::window::Window;
 
 use crate::camera::Camera;
+use crate::common_gl::{ColorFormat, FramebufferError, LiveObjects, ShaderError, ShaderProgram};
+use crate::frame_clock::FrameClock;
+use crate::shaders::{build_program, shader_source, ShaderSource};
 
 // shaders
-const SRC_FRAG_BLUR: &[u8] = include_bytes!("../assets/shaders/blur.frag");
-const SRC_FRAG_DITHER: &[u8] = include_bytes!("../assets/shaders/dither.frag");
-const SRC_FRAG_KAWASE: &[u8] = include_bytes!("../assets/shaders/kawase.frag");
-const SRC_VERT_QUAD: &[u8] = include_bytes!("../assets/shaders/quad.vert");
-const SRC_VERT_ROUND_RECT: &[u8] = include_bytes!("../assets/shaders/round-rect.vert");
-const SRC_FRAG_ROUND_RECT: &[u8] = include_bytes!("../assets/shaders/round-rect.frag");
-const SRC_VERT_SCREEN: &[u8] = include_bytes!("../assets/shaders/screen.vert");
-const SRC_FRAG_TEXTURE: &[u8] = include_bytes!("../assets/shaders/texture.frag");
+const SRC_FRAG_BLUR: ShaderSource = shader_source!("blur.frag");
+const SRC_FRAG_BOX: ShaderSource = shader_source!("box.frag");
+const SRC_FRAG_DITHER: ShaderSource =
+    shader_source!("texture.frag").with_defines(&[("DITHER", "1")]);
+const SRC_FRAG_KAWASE: ShaderSource = shader_source!("kawase.frag");
+const SRC_VERT_QUAD: ShaderSource = shader_source!("quad.vert");
+const SRC_VERT_ROUND_RECT: ShaderSource = shader_source!("round-rect.vert");
+const SRC_VERT_ROUND_RECT_INSTANCED: ShaderSource = shader_source!("round-rect-instanced.vert");
+const SRC_VERT_ROUND_QUADS_UPDATE: ShaderSource = shader_source!("round-quads-update.vert");
+const SRC_FRAG_ROUND_RECT: ShaderSource = shader_source!("round-rect.frag");
+const SRC_FRAG_SAT_BUILD: ShaderSource = shader_source!("sat-build.frag");
+const SRC_FRAG_SAT_BLUR: ShaderSource = shader_source!("sat-blur.frag");
+const SRC_VERT_SCREEN: ShaderSource =
+    shader_source!("quad.vert").with_defines(&[("SCREEN_SPACE", "1")]);
+const SRC_FRAG_TEXTURE: ShaderSource = shader_source!("texture.frag");
 
 // images
 const GURA_JPG: &[u8] = include_bytes!("../assets/gura.jpg");
-// const BIG_SQUARES_PNG: &[u8] = include_bytes!("../../assets/big-squares.png");
+const BIG_SQUARES_PNG: &[u8] = include_bytes!("../assets/big-squares.png");
+
+/// Images that can be blurred without loading anything from disk.
+pub const BUNDLED_IMAGES: &[(&str, &[u8])] =
+    &[("gura.jpg", GURA_JPG), ("big-squares.png", BIG_SQUARES_PNG)];
+
+/// Image shown by the blur scenes.
+#[derive(Debug, Clone)]
+pub enum InputImage {
+    /// Index in [`BUNDLED_IMAGES`].
+    Bundled(usize),
+    File(PathBuf),
+}
+
+impl Default for InputImage {
+    fn default() -> Self {
+        Self::Bundled(0)
+    }
+}
+
+impl fmt::Display for InputImage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bundled(index) => f.write_str(BUNDLED_IMAGES[*index].0),
+            Self::File(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+impl InputImage {
+    pub fn load(&self) -> Result<RgbaImage, image::ImageError> {
+        let image = match self {
+            Self::Bundled(index) => image::load_from_memory(BUNDLED_IMAGES[*index].1)?,
+            Self::File(path) => image::open(path)?,
+        };
+
+        Ok(image.into_rgba8())
+    }
+
+    /// Same as [`Self::load`], falling back to the first bundled image.
+    pub fn load_or_default(&self) -> RgbaImage {
+        self.load().unwrap_or_else(|e| {
+            eprintln!("Error loading image {self}: {e}");
+            Self::default().load().unwrap()
+        })
+    }
+}
+
+/// Settings given to every scene when it gets created.
+#[derive(Debug, Clone, Default)]
+pub struct SceneOptions {
+    /// Seed for scenes with random content, picked at random when `None`.
+    pub seed: Option<u64>,
+    /// Number of quads in the round quads scene.
+    pub quad_count: Option<usize>,
+    /// How the round quads scene gets its quads to the GPU.
+    pub quad_mode: Option<QuadMode>,
+    /// Corners, fill and shadow of the round quads.
+    pub quad_style: Option<QuadStyle>,
+    /// Frame time budget in milliseconds of a stress test that the round quads
+    /// scene starts right away.
+    pub stress_budget_ms: Option<f32>,
+    /// Whether frames wait for vertical sync, in which case their times say
+    /// nothing about the cost of a scene.
+    pub vsync: bool,
+    pub blur: BlurOptions,
+    pub image: InputImage,
+}
+
+/// Overrides for the default parameters of the blur scenes. Each scene clamps
+/// them to its own limits, and ignores the ones it doesn't have.
+#[derive(Debug, Clone, Default)]
+pub struct BlurOptions {
+    pub kernel: Option<i32>,
+    pub radius: Option<f32>,
+    pub layers: Option<usize>,
+    pub dither: Option<bool>,
+    /// Format of the framebuffers that the image is blurred through.
+    pub format: Option<ColorFormat>,
+}
+
+/// Why a scene couldn't be created, or couldn't take a new image.
+#[derive(Debug, Clone)]
+pub enum SceneError {
+    Shader(ShaderError),
+    Framebuffer(FramebufferError),
+}
+
+impl From<ShaderError> for SceneError {
+    fn from(e: ShaderError) -> Self {
+        Self::Shader(e)
+    }
+}
+
+impl From<FramebufferError> for SceneError {
+    fn from(e: FramebufferError) -> Self {
+        Self::Framebuffer(e)
+    }
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Shader(e) => e.fmt(f),
+            Self::Framebuffer(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for SceneError {}
+
+/// Scene that can be registered in a [`SceneRegistry`] and switched to at runtime.
+pub trait Scene {
+    /// Creates the scene's GL objects, or fails if one of its shader programs
+    /// doesn't build or one of its framebuffers can't be created.
+    fn new(size: PhysicalSize<u32>, options: &SceneOptions) -> Result<Self, SceneError>
+    where
+        Self: Sized;
+
+    /// Name under which the scene is registered.
+    fn name() -> &'static str
+    where
+        Self: Sized;
+
+    fn on_key(&mut self, _keycode: Key<SmolStr>) {}
+
+    /// Handles a mouse button, with the pointer in window coordinates. Returns
+    /// whether the scene used it, in which case it doesn't move the camera.
+    fn on_mouse_button(
+        &mut self,
+        _camera: &Camera,
+        _pointer: Vec2,
+        _button: MouseButton,
+        _state: ElementState,
+    ) -> bool {
+        false
+    }
+
+    /// Recompiles the scene's shader programs from their current sources. If
+    /// any of them fails, the old programs are kept and the error is returned.
+    fn reload_shaders(&mut self) -> Result<(), ShaderError> {
+        Ok(())
+    }
+
+    /// Replaces the input image, for scenes that have one. If the scene can't
+    /// make room for it, the old image is kept and the error is returned.
+    fn set_image(&mut self, _image: &RgbaImage) -> Result<(), SceneError> {
+        Ok(())
+    }
+
+    /// Short description of the scene's current parameters, used in logs and
+    /// screenshot names. Empty for scenes without parameters.
+    fn params(&self) -> String {
+        String::new()
+    }
+
+    /// Format the scene renders its framebuffers in, for scenes that have a
+    /// choice. It can differ from the requested one when the driver can't
+    /// render to that.
+    fn color_format(&self) -> Option<ColorFormat> {
+        None
+    }
+
+    fn draw(&mut self, camera: &Camera, clock: &FrameClock, mouse_pos: Vec2);
+
+    fn resize(&mut self, camera: &Camera, width: i32, height: i32);
+}
+
+type CreateScene = fn(PhysicalSize<u32>, &SceneOptions) -> Result<Box<dyn Scene>, SceneError>;
+
+/// How to create a scene. Each scene module has one as `SCENE`.
+#[derive(Clone, Copy)]
+pub struct SceneEntry {
+    name: fn() -> &'static str,
+    create: CreateScene,
+}
+
+impl SceneEntry {
+    pub const fn new<S: Scene + 'static>() -> Self {
+        Self {
+            name: S::name,
+            create: create_scene::<S>,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        (self.name)()
+    }
+}
+
+fn create_scene<S: Scene + 'static>(
+    size: PhysicalSize<u32>,
+    options: &SceneOptions,
+) -> Result<Box<dyn Scene>, SceneError> {
+    Ok(Box::new(S::new(size, options)?))
+}
+
+/// List of every scene the app can switch to, in F-key order.
+#[derive(Default)]
+pub struct SceneRegistry {
+    entries: Vec<SceneEntry>,
+}
+
+impl SceneRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<S: Scene + 'static>(&mut self) -> &mut Self {
+        self.entries.push(SceneEntry::new::<S>());
+        self
+    }
+
+    pub fn entries(&self) -> &[SceneEntry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
 
-pub enum Scenes {
-    RoundQuads(RoundQuadsScene),
-    Blurring(BlurringScene),
-    Kawase(KawaseScene),
+    /// Finds a scene by name, ignoring case.
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        (self.entries.iter()).position(|entry| entry.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Same as [`Self::index_of`], with an error listing the available scenes.
+    pub fn find(&self, name: &str) -> Result<usize, String> {
+        self.index_of(name).ok_or_else(|| {
+            let names = self.entries.iter().map(|entry| entry.name());
+            let names = names.collect::<Vec<_>>().join(", ");
+            format!("unknown scene {name:?} (available: {names})")
+        })
+    }
+}
+
+/// Registry with all the scenes of the playground.
+pub fn default_registry() -> SceneRegistry {
+    SceneRegistry {
+        entries: SCENES.to_vec(),
+    }
+}
+
+const SCENE_KEYS: &[NamedKey] = &[
+    NamedKey::F1,
+    NamedKey::F2,
+    NamedKey::F3,
+    NamedKey::F4,
+    NamedKey::F5,
+    NamedKey::F6,
+    NamedKey::F7,
+    NamedKey::F8,
+    NamedKey::F9,
+    NamedKey::F10,
+    NamedKey::F11,
+    NamedKey::F12,
+];
+
+pub struct Scenes {
+    registry: SceneRegistry,
+    options: SceneOptions,
+    current: usize,
+    scene: Box<dyn Scene>,
+    /// Why the current scene couldn't be created. An empty scene is shown in
+    /// its place until its shaders are fixed.
+    error: Option<SceneError>,
+    /// GL objects that were alive before the current scene was created, to
+    /// find the ones it leaks.
+    live_before: LiveObjects,
 }
 
 impl Scenes {
-    pub fn new(window: &Window) -> Self {
-        Self::Kawase(KawaseScene::new(window))
+    pub fn new(
+        size: PhysicalSize<u32>,
+        registry: SceneRegistry,
+        options: SceneOptions,
+        initial: usize,
+    ) -> Self {
+        assert!(!registry.is_empty(), "no scene registered");
+
+        let mut scenes = Self {
+            current: initial.min(registry.len() - 1),
+            registry,
+            options,
+            scene: Box::new(EmptyScene),
+            error: None,
+            live_before: LiveObjects::now(),
+        };
+
+        scenes.switch_to(size, scenes.current);
+        scenes
+    }
+
+    pub fn registry(&self) -> &SceneRegistry {
+        &self.registry
+    }
+
+    pub fn current_index(&self) -> usize {
+        self.current
+    }
+
+    pub fn current_name(&self) -> &'static str {
+        self.registry.entries[self.current].name()
+    }
+
+    /// Why the current scene couldn't be created, if it failed.
+    pub fn error(&self) -> Option<&SceneError> {
+        self.error.as_ref()
+    }
+
+    /// Recreates the scene at `index`, even if it is the current one. If it
+    /// fails to build, the error is reported and an empty scene is shown.
+    pub fn switch_to(&mut self, size: PhysicalSize<u32>, index: usize) {
+        if let Some(entry) = self.registry.entries.get(index) {
+            // drop the old scene first so that its GL objects are freed before
+            // the new one allocates its own
+            self.scene = Box::new(EmptyScene);
+
+            if cfg!(debug_assertions) {
+                self.report_leaks();
+            }
+
+            self.current = index;
+            self.live_before = LiveObjects::now();
+
+            match (entry.create)(size, &self.options) {
+                Ok(scene) => {
+                    self.scene = scene;
+                    self.error = None;
+                    println!("scene: {}", entry.name());
+                }
+                Err(e) => {
+                    eprintln!("Error creating scene {}: {e}", entry.name());
+                    self.error = Some(e);
+                }
+            }
+        }
+    }
+
+    /// Warns about the GL objects that the last scene left alive after being
+    /// dropped.
+    fn report_leaks(&self) {
+        let leaked = LiveObjects::leaked_since(self.live_before);
+
+        if !leaked.is_empty() {
+            let leaked = (leaked.iter())
+                .map(|(kind, count)| format!("{count} {kind}(s)"))
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            eprintln!("Warning: scene {} leaked {leaked}", self.current_name());
+        }
+    }
+
+    pub fn switch_to_name(&mut self, size: PhysicalSize<u32>, name: &str) -> bool {
+        match self.registry.index_of(name) {
+            Some(index) => {
+                self.switch_to(size, index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn next(&mut self, size: PhysicalSize<u32>) {
+        let index = (self.current + 1) % self.registry.len();
+        self.switch_to(size, index);
+    }
+
+    pub fn prev(&mut self, size: PhysicalSize<u32>) {
+        let index = (self.current + self.registry.len() - 1) % self.registry.len();
+        self.switch_to(size, index);
     }
 
-    pub fn switch_scene(&mut self, window: &Window, keycode: Key<SmolStr>) {
+    pub fn switch_scene(&mut self, size: PhysicalSize<u32>, keycode: Key<SmolStr>) {
         match keycode {
-            Key::Named(NamedKey::F1) => *self = Self::RoundQuads(RoundQuadsScene::new(window)),
-            Key::Named(NamedKey::F2) => *self = Self::Blurring(BlurringScene::new(window)),
-            Key::Named(NamedKey::F3) => *self = Self::Kawase(KawaseScene::new(window)),
+            Key::Named(NamedKey::PageDown) => self.next(size),
+            Key::Named(NamedKey::PageUp) => self.prev(size),
+            Key::Named(key) => {
+                if let Some(index) = SCENE_KEYS.iter().position(|&k| k == key) {
+                    self.switch_to(size, index);
+                }
+            }
             _ => (),
         }
     }
 
     pub fn on_key(&mut self, keycode: Key<SmolStr>) {
-        match self {
-            Self::RoundQuads(_) => {}
-            Self::Blurring(scene) => scene.on_key(keycode),
-            Self::Kawase(scene) => scene.on_key(keycode),
+        self.scene.on_key(keycode);
+    }
+
+    pub fn on_mouse_button(
+        &mut self,
+        camera: &Camera,
+        pointer: Vec2,
+        button: MouseButton,
+        state: ElementState,
+    ) -> bool {
+        self.scene.on_mouse_button(camera, pointer, button, state)
+    }
+
+    /// Loads an image and gives it to the current scene and the next ones. The
+    /// current image is kept if it can't be loaded, or if the scene can't take
+    /// it.
+    pub fn set_image(&mut self, image: InputImage) {
+        let rgba = match image.load() {
+            Ok(rgba) => rgba,
+            Err(e) => return eprintln!("Error loading image {image}: {e}"),
+        };
+
+        match self.scene.set_image(&rgba) {
+            Ok(()) => {
+                println!("image: {image} ({}x{})", rgba.width(), rgba.height());
+                self.options.image = image;
+            }
+            Err(e) => eprintln!("Error setting image {image}: {e}"),
         }
     }
 
-    pub fn draw(&mut self, camera: &Camera, mouse_pos: Vec2) {
-        match self {
-            Self::RoundQuads(scene) => scene.draw(camera, mouse_pos),
-            Self::Blurring(scene) => scene.draw(camera, mouse_pos),
-            Self::Kawase(scene) => scene.draw(camera, mouse_pos),
+    /// Switches to the next bundled image, or the first one after a file.
+    pub fn next_bundled_image(&mut self) {
+        let next = match self.options.image {
+            InputImage::Bundled(index) => (index + 1) % BUNDLED_IMAGES.len(),
+            InputImage::File(_) => 0,
+        };
+
+        self.set_image(InputImage::Bundled(next));
+    }
+
+    /// Recompiles the shaders of the current scene, or tries to create it
+    /// again if it failed to build.
+    pub fn reload_shaders(&mut self, size: PhysicalSize<u32>) -> Result<(), SceneError> {
+        if self.error.is_some() {
+            self.switch_to(size, self.current);
+            return self.error.clone().map_or(Ok(()), Err);
         }
+
+        Ok(self.scene.reload_shaders()?)
+    }
+
+    pub fn current_params(&self) -> String {
+        self.scene.params()
+    }
+
+    pub fn current_color_format(&self) -> Option<ColorFormat> {
+        self.scene.color_format()
+    }
+
+    pub fn draw(&mut self, camera: &Camera, clock: &FrameClock, mouse_pos: Vec2) {
+        self.scene.draw(camera, clock, mouse_pos);
     }
 
     pub fn resize(&mut self, camera: &Camera, width: i32, height: i32) {
-        match self {
-            Self::RoundQuads(scene) => scene.resize(camera, width, height),
-            Self::Blurring(scene) => scene.resize(camera, width, height),
-            Self::Kawase(scene) => scene.resize(camera, width, height),
-        }
+        self.scene.resize(camera, width, height);
+    }
+}
+
+/// Compiles a program for each pair of vertex and fragment shaders, or none of
+/// them if one fails.
+unsafe fn create_programs<const N: usize>(
+    sources: [(&ShaderSource, &ShaderSource); N],
+) -> Result<[ShaderProgram; N], ShaderError> {
+    let mut programs = Vec::with_capacity(N);
+
+    // the programs built before a failing one are deleted when dropped
+    for (vert, frag) in sources {
+        programs.push(build_program(vert, frag)?);
     }
+
+    Ok(programs.try_into().unwrap())
+}
+
+/// Placeholder held while switching scenes.
+struct EmptyScene;
+
+impl Scene for EmptyScene {
+    fn new(_size: PhysicalSize<u32>, _options: &SceneOptions) -> Result<Self, SceneError> {
+        Ok(Self)
+    }
+
+    fn name() -> &'static str {
+        "Empty"
+    }
+
+    fn draw(&mut self, _camera: &Camera, _clock: &FrameClock, _mouse_pos: Vec2) {}
+
+    fn resize(&mut self, _camera: &Camera, _width: i32, _height: i32) {}
 }
@@ -0,0 +1,588 @@
+//! Shared machinery of the blur scenes: the input image, the pyramid of
+//! framebuffers it's blurred through, the full-screen passes between them and
+//! the final draw to the screen.
+//!
+//! A [`BlurTechnique`] only says how one level is blurred into the next on the
+//! way down and on the way up, and [`BlurPipeline`] turns it into a scene.
+
+// the unsafe functions all need a current GL context, like the ones of
+// `common_gl`
+#![allow(clippy::missing_safety_doc)]
+
+use std::ffi::CStr;
+use std::mem;
+
+use gl::types::{GLsizei, GLsizeiptr};
+use glam::{uvec2, vec2, Mat4, UVec2, Vec2};
+use image::RgbaImage;
+use winit::dpi::PhysicalSize;
+use winit::keyboard::{Key, SmolStr};
+
+use crate::camera::Camera;
+use crate::common_gl::{
+    bind_screen_framebuffer, pop_debug_group, push_debug_group, upload_texture, vertex_layout,
+    Buffer, ColorFormat, Framebuffer, FramebufferDesc, FramebufferError, ShaderError,
+    ShaderProgram, Texture, VertexArray,
+};
+use crate::frame_clock::FrameClock;
+
+use super::{
+    create_programs, BlurOptions, Scene, SceneError, SceneOptions, SRC_FRAG_DITHER,
+    SRC_FRAG_TEXTURE, SRC_VERT_QUAD, SRC_VERT_SCREEN,
+};
+
+/// Resolution divisor of each level of the pyramid.
+pub const RESDIVS: &[u32] = &[2, 4, 8, 16, 32, 64];
+
+/// Largest blur radius the techniques accept.
+pub const MAX_RADIUS: f32 = RESDIVS[RESDIVS.len() - 1] as f32 / 2.0;
+
+/// How a blur goes down the pyramid and back up.
+///
+/// The image is first copied to the first level. With `layers` layers, the
+/// downsampling passes then go through the next `layers` levels, or through
+/// the first `layers` levels when [`Self::BLURS_FIRST_LEVEL`] is set. The
+/// upsampling passes go back up to the first level.
+pub trait BlurTechnique: Sized {
+    /// Name of the scene.
+    const NAME: &'static str;
+    const DEFAULT_LAYERS: usize;
+    /// Whether the first downsampling pass stays at the resolution of the
+    /// first level.
+    const BLURS_FIRST_LEVEL: bool;
+    /// Whether the levels need a second framebuffer for separable passes.
+    const SEPARABLE: bool;
+    /// Most layers the technique blurs through, when it's fewer than the
+    /// pyramid has.
+    const MAX_LAYERS: usize = usize::MAX;
+
+    const DOWNSAMPLING_LABEL: &'static CStr;
+    const UPSAMPLING_LABEL: &'static CStr;
+
+    fn new(options: &BlurOptions) -> Result<Self, ShaderError>;
+
+    /// Recompiles the programs, keeping the old ones if any of them fails.
+    fn reload_shaders(&mut self) -> Result<(), ShaderError>;
+
+    /// Programs drawn with [`PassRunner`], whose vertex layout it binds.
+    fn programs(&self) -> Vec<&ShaderProgram>;
+
+    /// Creates the framebuffers the technique needs besides the pyramid, every
+    /// time the pyramid is created. `size` is the size of its first level.
+    unsafe fn create_targets(&mut self, _size: UVec2) {}
+
+    /// Handles the keys of the technique's own parameters.
+    fn on_key(&mut self, _keycode: &Key<SmolStr>) -> bool {
+        false
+    }
+
+    /// Parameters of the technique, with the number of layers.
+    fn params(&self, layers: usize) -> String;
+
+    unsafe fn downsample(&self, runner: &PassRunner, from: &Framebuffer, to: &Level);
+
+    /// Draws the level below into `to` on the way back up. Does nothing by
+    /// default, for techniques whose result is already in the first level
+    /// when they're done going down.
+    unsafe fn upsample(&self, _runner: &PassRunner, _from: &Framebuffer, _to: &Level) {}
+}
+
+/// Level of the pyramid.
+pub struct Level {
+    pub fb: Framebuffer,
+    /// Target of the first half of separable passes.
+    pub ping_pong: Option<Framebuffer>,
+}
+
+/// Draws full-screen triangles from one framebuffer into another.
+pub struct PassRunner {
+    vao: VertexArray,
+    _vbo: Buffer,
+}
+
+impl PassRunner {
+    unsafe fn new() -> Self {
+        let vao = VertexArray::new();
+        gl::BindVertexArray(vao.id());
+
+        let vbo = Buffer::new();
+        gl::BindBuffer(gl::ARRAY_BUFFER, vbo.id());
+        gl::BufferData(
+            gl::ARRAY_BUFFER,
+            mem::size_of_val(SCREEN_VERTICES) as GLsizeiptr,
+            SCREEN_VERTICES.as_ptr() as *const _,
+            gl::STATIC_DRAW,
+        );
+
+        Self { vao, _vbo: vbo }
+    }
+
+    unsafe fn bind_vertex_layouts(&self, programs: &[&ShaderProgram]) {
+        gl::BindVertexArray(self.vao.id());
+        for program in programs {
+            program.bind_vertex_layout::<Vertex>();
+        }
+    }
+
+    /// Clears `target` and draws `input` over all of it with the bound
+    /// program.
+    pub unsafe fn draw(&self, input: &Texture, target: &Framebuffer) {
+        gl::BindFramebuffer(gl::FRAMEBUFFER, target.fbo);
+        gl::Viewport(0, 0, target.size.x as i32, target.size.y as i32);
+
+        gl::ClearColor(0.0, 0.0, 0.0, 0.0);
+        gl::Clear(gl::COLOR_BUFFER_BIT);
+
+        gl::BindVertexArray(self.vao.id());
+        gl::ActiveTexture(gl::TEXTURE0);
+        gl::BindTexture(gl::TEXTURE_2D, input.id());
+        gl::DrawArrays(gl::TRIANGLES, 0, SCREEN_VERTICES.len() as GLsizei);
+    }
+
+    /// Draws `input` into the ping-pong framebuffer of `target`, then that
+    /// into `target`. `bind_axis` binds the program of each half with its
+    /// uniforms, given 0 for the first one and 1 for the second one.
+    pub unsafe fn separable(&self, input: &Framebuffer, target: &Level, bind_axis: impl Fn(u32)) {
+        let ping_pong = (target.ping_pong.as_ref()).expect("separable pass without ping-pong");
+
+        bind_axis(0);
+        self.draw(input.texture(), ping_pong);
+
+        bind_axis(1);
+        self.draw(ping_pong.texture(), &target.fb);
+    }
+}
+
+/// Scene blurring an image with a [`BlurTechnique`].
+pub struct BlurPipeline<T> {
+    technique: T,
+
+    matrix: Mat4,
+    viewport: Vec2,
+
+    quad_shader: ShaderProgram,
+    dither_shader: ShaderProgram,
+    copy_shader: ShaderProgram,
+    quad_vao: VertexArray,
+    quad_vbo: Buffer,
+    _quad_ebo: Buffer,
+
+    runner: PassRunner,
+    levels: Vec<Level>,
+
+    image_texture: Texture,
+    image_size: UVec2,
+
+    layers: usize,
+    is_dithered: bool,
+    /// Format of the pyramid. The image is only quantized to 8 bits in the
+    /// final pass when it's a float format.
+    format: ColorFormat,
+}
+
+impl<T: BlurTechnique> Scene for BlurPipeline<T> {
+    fn new(size: PhysicalSize<u32>, options: &SceneOptions) -> Result<Self, SceneError> {
+        let [quad_shader, dither_shader, copy_shader] = unsafe { Self::create_programs()? };
+        let mut technique = T::new(&options.blur)?;
+
+        let PhysicalSize { width, height } = size;
+        let viewport = Vec2::new(width as f32, height as f32);
+
+        let image = options.image.load_or_default();
+        let image_size = uvec2(image.width(), image.height());
+
+        unsafe {
+            let image_texture = Texture::new();
+            upload_texture(
+                image_texture.id(),
+                image.width(),
+                image.height(),
+                image.as_ptr(),
+                gl::CLAMP_TO_BORDER,
+            );
+
+            // Normal blending
+            gl::Enable(gl::BLEND);
+            gl::BlendEquation(gl::FUNC_ADD);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+
+            // framebuffers
+            let mut format = options.blur.format.unwrap_or_default();
+            let levels = Self::create_levels(image_size, &mut format)?;
+            technique.create_targets(levels[0].fb.size);
+
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+
+            // image quad
+            let quad_vao = VertexArray::new();
+            gl::BindVertexArray(quad_vao.id());
+
+            let vertices = image_quad(image_size);
+            let quad_vbo = Buffer::new();
+            gl::BindBuffer(gl::ARRAY_BUFFER, quad_vbo.id());
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                mem::size_of_val(&vertices) as GLsizeiptr,
+                vertices.as_ptr() as *const _,
+                gl::DYNAMIC_DRAW,
+            );
+
+            let quad_ebo = Buffer::new();
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, quad_ebo.id());
+            gl::BufferData(
+                gl::ELEMENT_ARRAY_BUFFER,
+                mem::size_of_val(QUAD_INDICES) as GLsizeiptr,
+                QUAD_INDICES.as_ptr() as *const _,
+                gl::STATIC_DRAW,
+            );
+
+            quad_shader.bind_vertex_layout::<Vertex>();
+            dither_shader.bind_vertex_layout::<Vertex>();
+
+            // full-screen passes
+            let runner = PassRunner::new();
+            runner.bind_vertex_layouts(&[&copy_shader]);
+            runner.bind_vertex_layouts(&technique.programs());
+
+            Ok(Self {
+                technique,
+
+                matrix: Mat4::default(),
+                viewport,
+
+                quad_shader,
+                dither_shader,
+                copy_shader,
+                quad_vao,
+                quad_vbo,
+                _quad_ebo: quad_ebo,
+
+                runner,
+                levels,
+
+                image_texture,
+                image_size,
+
+                layers: (options.blur.layers)
+                    .unwrap_or(T::DEFAULT_LAYERS)
+                    .min(Self::max_layers()),
+                is_dithered: options.blur.dither.unwrap_or(false),
+                format,
+            })
+        }
+    }
+
+    fn name() -> &'static str {
+        T::NAME
+    }
+
+    fn on_key(&mut self, keycode: Key<SmolStr>) {
+        if !self.technique.on_key(&keycode) {
+            let Key::Character(ch) = &keycode else {
+                return;
+            };
+
+            match ch.as_str() {
+                "d" | "D" => {
+                    self.is_dithered = !self.is_dithered;
+                }
+                "f" | "F" => {
+                    let formats = ColorFormat::ALL;
+                    let i = (formats.iter())
+                        .position(|&format| format == self.format)
+                        .unwrap_or_default();
+
+                    let format = formats[(i + 1) % formats.len()];
+                    if let Err(e) = self.recreate_levels(self.image_size, format) {
+                        eprintln!("{e}");
+                    }
+                }
+                "l" => {
+                    self.layers = (self.layers + 1).min(Self::max_layers());
+                }
+                "L" => {
+                    self.layers = self.layers.saturating_sub(1);
+                }
+                _ => (),
+            }
+        }
+    }
+
+    fn params(&self) -> String {
+        let dither_mode = if self.is_dithered { " dithering" } else { "" };
+
+        let format = match self.format {
+            ColorFormat::Rgba8 => String::new(),
+            format => format!(" {format}"),
+        };
+
+        format!(
+            "{}{}{}",
+            self.technique.params(self.layers),
+            dither_mode,
+            format
+        )
+    }
+
+    fn color_format(&self) -> Option<ColorFormat> {
+        Some(self.format)
+    }
+
+    fn reload_shaders(&mut self) -> Result<(), ShaderError> {
+        unsafe {
+            let [quad_shader, dither_shader, copy_shader] = Self::create_programs()?;
+            self.technique.reload_shaders()?;
+
+            self.quad_shader = quad_shader;
+            self.dither_shader = dither_shader;
+            self.copy_shader = copy_shader;
+
+            // attribute locations can change between compilations
+            gl::BindVertexArray(self.quad_vao.id());
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.quad_vbo.id());
+            self.quad_shader.bind_vertex_layout::<Vertex>();
+            self.dither_shader.bind_vertex_layout::<Vertex>();
+
+            self.runner.bind_vertex_layouts(&[&self.copy_shader]);
+            self.runner.bind_vertex_layouts(&self.technique.programs());
+        }
+
+        Ok(())
+    }
+
+    fn set_image(&mut self, image: &RgbaImage) -> Result<(), SceneError> {
+        let image_size = uvec2(image.width(), image.height());
+
+        // the pyramid follows the size of the image
+        self.recreate_levels(image_size, self.format)?;
+
+        unsafe {
+            upload_texture(
+                self.image_texture.id(),
+                image.width(),
+                image.height(),
+                image.as_ptr(),
+                gl::CLAMP_TO_BORDER,
+            );
+
+            let vertices = image_quad(image_size);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.quad_vbo.id());
+            gl::BufferSubData(
+                gl::ARRAY_BUFFER,
+                0,
+                mem::size_of_val(&vertices) as GLsizeiptr,
+                vertices.as_ptr() as *const _,
+            );
+        }
+
+        Ok(())
+    }
+
+    fn draw(&mut self, _camera: &Camera, _clock: &FrameClock, _mouse_pos: Vec2) {
+        unsafe {
+            let texture = if self.layers == 0 {
+                push_debug_group(c"Draw normally");
+
+                &self.image_texture
+            } else {
+                push_debug_group(c"Draw with blurring");
+                self.blur()
+            };
+
+            // draw framebuffer to screen as quad
+            push_debug_group(c"Final draw to quad");
+            {
+                gl::Enable(gl::BLEND);
+                bind_screen_framebuffer();
+                gl::Viewport(0, 0, self.viewport.x as i32, self.viewport.y as i32);
+
+                gl::ClearColor(0.0, 0.2, 0.15, 0.5);
+                gl::Clear(gl::COLOR_BUFFER_BIT);
+                if self.is_dithered {
+                    self.dither_shader.bind();
+                } else {
+                    self.quad_shader.bind();
+                }
+
+                gl::BindVertexArray(self.quad_vao.id());
+                gl::BindTexture(gl::TEXTURE_2D, texture.id());
+                gl::DrawElements(
+                    gl::TRIANGLES,
+                    QUAD_INDICES.len() as GLsizei,
+                    gl::UNSIGNED_INT,
+                    std::ptr::null(),
+                );
+            }
+            pop_debug_group();
+
+            pop_debug_group(); // Draw normally / with blurring
+        }
+    }
+
+    fn resize(&mut self, camera: &Camera, width: i32, height: i32) {
+        unsafe {
+            gl::Viewport(0, 0, width, height);
+
+            self.viewport = Vec2::new(width as f32, height as f32);
+            self.matrix = camera.matrix(self.viewport);
+
+            self.quad_shader.bind();
+            self.quad_shader.set("u_mvp", self.matrix);
+
+            self.dither_shader.bind();
+            self.dither_shader.set("u_mvp", self.matrix);
+        }
+    }
+}
+
+impl<T: BlurTechnique> BlurPipeline<T> {
+    unsafe fn create_programs() -> Result<[ShaderProgram; 3], ShaderError> {
+        create_programs([
+            (&SRC_VERT_QUAD, &SRC_FRAG_TEXTURE),
+            (&SRC_VERT_QUAD, &SRC_FRAG_DITHER),
+            (&SRC_VERT_SCREEN, &SRC_FRAG_TEXTURE),
+        ])
+    }
+
+    fn max_layers() -> usize {
+        let max_layers = if T::BLURS_FIRST_LEVEL {
+            RESDIVS.len()
+        } else {
+            RESDIVS.len() - 1
+        };
+
+        max_layers.min(T::MAX_LAYERS)
+    }
+
+    /// Creates the pyramid in `format`, or in RGBA8 if the driver can't
+    /// render to it.
+    unsafe fn create_levels(
+        image_size: UVec2,
+        format: &mut ColorFormat,
+    ) -> Result<Vec<Level>, FramebufferError> {
+        let create = |format| -> Result<Vec<_>, FramebufferError> {
+            (RESDIVS.iter().copied())
+                .map(|resdiv| {
+                    let size = (image_size / resdiv).max(UVec2::ONE);
+                    let desc = |name| FramebufferDesc::new(name, size).color_format(format);
+
+                    Ok(Level {
+                        fb: desc("composite").create()?,
+                        ping_pong: if T::SEPARABLE {
+                            Some(desc("ping_pong").create()?)
+                        } else {
+                            None
+                        },
+                    })
+                })
+                .collect()
+        };
+
+        match create(*format) {
+            Err(e) if *format != ColorFormat::Rgba8 => {
+                eprintln!("Warning: {e}, blurring in RGBA8 instead");
+                *format = ColorFormat::Rgba8;
+                create(*format)
+            }
+            result => result,
+        }
+    }
+
+    /// Replaces the pyramid with one for an image of `image_size` in `format`.
+    /// The old one is kept if the new one can't be created.
+    fn recreate_levels(
+        &mut self,
+        image_size: UVec2,
+        mut format: ColorFormat,
+    ) -> Result<(), FramebufferError> {
+        unsafe {
+            let levels = Self::create_levels(image_size, &mut format);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+
+            self.levels = levels?;
+            self.image_size = image_size;
+            self.format = format;
+
+            self.technique.create_targets(self.levels[0].fb.size);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+
+        Ok(())
+    }
+
+    /// Runs the technique through the pyramid, and returns the texture of the
+    /// first level where the result ends up.
+    unsafe fn blur(&self) -> &Texture {
+        // the passes overwrite their target, blending would multiply the
+        // alpha by itself every time
+        gl::Disable(gl::BLEND);
+
+        // draw image to framebuffer
+        push_debug_group(c"Image to framebuffer");
+        self.copy_shader.bind();
+        self.runner.draw(&self.image_texture, &self.levels[0].fb);
+        pop_debug_group();
+
+        let first = if T::BLURS_FIRST_LEVEL { 0 } else { 1 };
+        let last = first + self.layers - 1;
+
+        // blur at half-resolution, then quarter-res, then eighth-res, ...
+        push_debug_group(T::DOWNSAMPLING_LABEL);
+        for fbi in first..=last {
+            // FBI OPEN UP
+            let from = &self.levels[fbi.saturating_sub(1)].fb;
+            self.technique
+                .downsample(&self.runner, from, &self.levels[fbi]);
+        }
+        pop_debug_group();
+
+        // ..., then eighth-res, then quarter-res, then half-resolution
+        push_debug_group(T::UPSAMPLING_LABEL);
+        for fbi in (0..last).rev() {
+            let from = &self.levels[fbi + 1].fb;
+            self.technique
+                .upsample(&self.runner, from, &self.levels[fbi]);
+        }
+        pop_debug_group();
+
+        self.levels[0].fb.texture()
+    }
+}
+
+fn image_quad(image_size: UVec2) -> [Vertex; 4] {
+    let size = image_size.as_vec2();
+
+    #[rustfmt::skip]
+    return [
+        Vertex::new(vec2(-0.5, -0.5) * size, vec2(0.0, 0.0)),
+        Vertex::new(vec2(-0.5,  0.5) * size, vec2(0.0, 1.0)),
+        Vertex::new(vec2( 0.5,  0.5) * size, vec2(1.0, 1.0)),
+        Vertex::new(vec2( 0.5, -0.5) * size, vec2(1.0, 0.0)),
+    ];
+}
+
+const QUAD_INDICES: &[u32] = &[0, 1, 2, 0, 2, 3];
+
+vertex_layout! {
+    /// Vertex used both for the image quad and for full-screen passes.
+    #[derive(Debug, Clone, Copy, Default)]
+    struct Vertex {
+        pub position: Vec2,
+        pub uv: Vec2,
+    }
+}
+
+impl Vertex {
+    const fn new(position: Vec2, uv: Vec2) -> Self {
+        Self { position, uv }
+    }
+}
+
+/// Triangle covering the whole viewport, the part outside of it being clipped.
+#[rustfmt::skip]
+const SCREEN_VERTICES: &[Vertex] = &[
+                  // position       // uv
+    Vertex::new(vec2(-1.0, -1.0), vec2(0.0, 0.0)),
+    Vertex::new(vec2( 3.0, -1.0), vec2(2.0, 0.0)),
+    Vertex::new(vec2(-1.0,  3.0), vec2(0.0, 2.0)),
+];
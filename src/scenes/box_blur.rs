@@ -0,0 +1,115 @@
+use std::ffi::CStr;
+
+use glam::vec2;
+use winit::keyboard::{Key, NamedKey, SmolStr};
+
+use crate::common_gl::{
+    pop_debug_group, push_debug_group, Framebuffer, ShaderError, ShaderProgram,
+};
+
+use super::blur_pipeline::{BlurPipeline, BlurTechnique, Level, PassRunner, MAX_RADIUS};
+use super::{create_programs, BlurOptions, SceneEntry, SRC_FRAG_BOX, SRC_VERT_SCREEN};
+
+pub type BoxBlurScene = BlurPipeline<BoxBlur>;
+
+pub const SCENE: SceneEntry = SceneEntry::new::<BoxBlurScene>();
+
+const MAX_ITERATIONS: i32 = 8;
+
+/// Box blur, repeated at every level. A few iterations already look close to a
+/// Gaussian blur.
+pub struct BoxBlur {
+    box_shader: ShaderProgram,
+
+    iterations: i32,
+    radius: f32,
+}
+
+impl BlurTechnique for BoxBlur {
+    const NAME: &'static str = "Box Blur";
+    const DEFAULT_LAYERS: usize = 2;
+    const BLURS_FIRST_LEVEL: bool = true;
+    const SEPARABLE: bool = true;
+
+    const DOWNSAMPLING_LABEL: &'static CStr = c"Box downsampling";
+    const UPSAMPLING_LABEL: &'static CStr = c"Box upsampling";
+
+    fn new(options: &BlurOptions) -> Result<Self, ShaderError> {
+        let [box_shader] = unsafe { create_programs([(&SRC_VERT_SCREEN, &SRC_FRAG_BOX)])? };
+
+        Ok(Self {
+            box_shader,
+
+            iterations: 3,
+            radius: options.radius.unwrap_or(2.0).clamp(0.0, MAX_RADIUS),
+        })
+    }
+
+    fn reload_shaders(&mut self) -> Result<(), ShaderError> {
+        let [box_shader] = unsafe { create_programs([(&SRC_VERT_SCREEN, &SRC_FRAG_BOX)])? };
+        self.box_shader = box_shader;
+        Ok(())
+    }
+
+    fn programs(&self) -> Vec<&ShaderProgram> {
+        vec![&self.box_shader]
+    }
+
+    fn on_key(&mut self, keycode: &Key<SmolStr>) -> bool {
+        match keycode {
+            Key::Named(NamedKey::ArrowUp) => {
+                self.iterations = (self.iterations + 1).min(MAX_ITERATIONS);
+            }
+            Key::Named(NamedKey::ArrowDown) => {
+                self.iterations = (self.iterations - 1).max(1);
+            }
+            Key::Named(NamedKey::ArrowRight) => {
+                self.radius = (self.radius + 0.1).min(MAX_RADIUS);
+            }
+            Key::Named(NamedKey::ArrowLeft) => {
+                self.radius = (self.radius - 0.1).max(0.0);
+            }
+            _ => return false,
+        }
+
+        true
+    }
+
+    fn params(&self, layers: usize) -> String {
+        format!("n={} r={:.2} l={}", self.iterations, self.radius, layers)
+    }
+
+    unsafe fn downsample(&self, runner: &PassRunner, from: &Framebuffer, to: &Level) {
+        self.box_passes(runner, from, to);
+    }
+
+    unsafe fn upsample(&self, runner: &PassRunner, from: &Framebuffer, to: &Level) {
+        self.box_passes(runner, from, to);
+    }
+}
+
+impl BoxBlur {
+    /// Blurs horizontally then vertically, `iterations` times.
+    unsafe fn box_passes(&self, runner: &PassRunner, from: &Framebuffer, to: &Level) {
+        let mut input = from;
+
+        for _ in 0..self.iterations {
+            push_debug_group(c"Box pass");
+
+            runner.separable(input, to, |axis| {
+                let direction = if axis == 0 {
+                    vec2(1.0, 0.0)
+                } else {
+                    vec2(0.0, 1.0)
+                };
+
+                self.box_shader.bind();
+                self.box_shader.set("u_direction", direction);
+                self.box_shader.set("u_radius", self.radius);
+            });
+            input = &to.fb;
+
+            pop_debug_group();
+        }
+    }
+}
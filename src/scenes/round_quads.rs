@@ -1,55 +1,224 @@
 use std::{
     f32::consts::{PI, TAU},
-    mem,
-    time::Instant,
+    fmt, mem,
+    ops::RangeInclusive,
 };
 
-use gl::types::{GLfloat, GLint, GLsizei, GLsizeiptr, GLuint};
-use glam::{vec2, Mat4, Vec2, Vec4};
-use rand::Rng;
-use winit::window::Window;
+use gl::types::{GLenum, GLfloat, GLsizei, GLsizeiptr};
+use glam::{vec2, vec4, Mat4, Vec2, Vec4};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use winit::{
+    dpi::PhysicalSize,
+    event::{ElementState, MouseButton},
+    keyboard::{Key, NamedKey, SmolStr},
+};
+
+use crate::{
+    camera::Camera,
+    common_gl::{
+        bind_screen_framebuffer, pop_debug_group, push_debug_group, vertex_layout, Buffer,
+        ShaderError, ShaderProgram, VertexArray, VertexLayout,
+    },
+    frame_clock::FrameClock,
+};
+
+use crate::shaders::build_feedback_program;
+
+use super::{
+    create_programs, Scene, SceneEntry, SceneError, SceneOptions, SRC_FRAG_ROUND_RECT,
+    SRC_VERT_ROUND_QUADS_UPDATE, SRC_VERT_ROUND_RECT, SRC_VERT_ROUND_RECT_INSTANCED,
+};
 
-use crate::{camera::Camera, common_gl::create_shader_program};
+const DEFAULT_QUAD_COUNT: usize = 100_000;
+/// Lowest quad count when it's changed at runtime. The command line can go
+/// lower, and the highest depends on the mode.
+const MIN_QUAD_COUNT: usize = 1_000;
+
+/// Frame time budget of the stress test when none is given, in milliseconds.
+pub const DEFAULT_STRESS_BUDGET_MS: f32 = 1000.0 / 60.0;
+/// Frames skipped after the quad count changes, while the buffers settle.
+const STRESS_WARMUP_FRAMES: u32 = 10;
+/// Frames averaged at each quad count.
+const STRESS_SAMPLE_FRAMES: usize = 60;
+
+/// Distance from the mouse under which quads spin.
+const SURROUND_RADIUS: f32 = 320.0;
+
+/// How the quads get to the GPU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QuadMode {
+    /// Four vertices per quad, each with a copy of all the quad's attributes,
+    /// and six indices.
+    #[default]
+    Vertices,
+    /// One [`Instance`] per quad, whose corners are expanded by the vertex
+    /// shader.
+    Instanced,
+    /// Instanced, and the quads are also spun on the GPU by transform feedback,
+    /// so nothing is uploaded after the first frame.
+    Gpu,
+}
 
-use super::{SRC_FRAG_ROUND_RECT, SRC_VERT_ROUND_RECT};
+impl QuadMode {
+    pub const ALL: [Self; 3] = [Self::Vertices, Self::Instanced, Self::Gpu];
 
-const N_QUADS: usize = 100_000;
+    fn next(self) -> Self {
+        match self {
+            Self::Vertices => Self::Instanced,
+            Self::Instanced => Self::Gpu,
+            Self::Gpu => Self::Vertices,
+        }
+    }
+
+    /// Highest quad count, about 300 MiB of vertices, and as much for each of
+    /// the two buffers in [`Self::Gpu`].
+    fn max_quad_count(self) -> usize {
+        match self {
+            Self::Vertices => 1 << 19,
+            Self::Instanced | Self::Gpu => 1 << 22,
+        }
+    }
+}
+
+impl fmt::Display for QuadMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Vertices => "vertices",
+            Self::Instanced => "instanced",
+            Self::Gpu => "gpu",
+        })
+    }
+}
+
+/// Corners, fill and shadow of the quads, on top of their random size and
+/// colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QuadStyle {
+    /// Same radius at every corner, flat fill and no shadow.
+    #[default]
+    Plain,
+    /// Rounder top corners, a vertical gradient and a drop shadow.
+    Cards,
+    /// Random radius at each corner, a flat, linear or radial fill, and no
+    /// shadow, an outer one or an inset one.
+    Random,
+}
+
+impl QuadStyle {
+    pub const ALL: [Self; 3] = [Self::Plain, Self::Cards, Self::Random];
+
+    fn next(self) -> Self {
+        match self {
+            Self::Plain => Self::Cards,
+            Self::Cards => Self::Random,
+            Self::Random => Self::Plain,
+        }
+    }
+}
+
+impl fmt::Display for QuadStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Plain => "plain",
+            Self::Cards => "cards",
+            Self::Random => "random",
+        })
+    }
+}
+
+pub const SCENE: SceneEntry = SceneEntry::new::<RoundQuadsScene>();
 
 pub struct RoundQuadsScene {
     matrix: Mat4,
     viewport: Vec2,
 
-    round_rect_shader: GLuint,
-    vao: GLuint,
-    vbo: GLuint,
-    ebo: GLuint,
-
-    u_mvp_quad: GLint,
+    mode: QuadMode,
+    round_rect_shader: ShaderProgram,
+    /// Only in [`QuadMode::Gpu`].
+    update_shader: Option<ShaderProgram>,
+    vao: VertexArray,
+    vbo: Buffer,
+    ebo: Buffer,
+    index_count: usize,
 
     quads: Vec<Quad>,
+    /// Only filled in [`QuadMode::Vertices`].
     vertices: Vec<[Vertex; 4]>,
-    indices: Vec<[u32; 6]>,
-
+    /// Only filled in [`QuadMode::Instanced`].
+    instances: Vec<Instance>,
+    /// Only in [`QuadMode::Gpu`], where the quads on the CPU are left behind
+    /// until switching to another mode.
+    animation: Option<GpuAnimation>,
+
+    /// Bytes written to the vertex buffer during the last frame.
+    uploaded_bytes: usize,
+
+    style: QuadStyle,
+    /// Seed the quads are created again from when the style changes.
+    seed: u64,
+    /// Generates the quads added when the count grows.
+    rng: StdRng,
     area_width: u32,
 
-    last_instant: Instant,
+    stress_test: Option<StressTest>,
+    /// Budget of the next stress test started with `T`.
+    stress_budget_ms: f32,
+    /// The stress test doesn't start with vertical sync, which would make
+    /// every frame last as long.
+    vsync: bool,
+
+    selected: Option<usize>,
+    drag: Option<Drag>,
+    /// Quads dragged away from their place in the grid, which the grid can't
+    /// find anymore.
+    moved: Vec<usize>,
 }
 
-impl RoundQuadsScene {
-    pub fn new(window: &Window) -> Self {
-        let area_width = (N_QUADS as f32).sqrt() as u32;
+/// Quad following the mouse while the button is held.
+#[derive(Debug, Clone, Copy)]
+struct Drag {
+    index: usize,
+    /// From the mouse to the center of the quad, in world coordinates.
+    offset: Vec2,
+}
 
-        let mut quads = Vec::with_capacity(N_QUADS);
-        let mut vertices = Vec::with_capacity(N_QUADS);
-        let mut indices = Vec::with_capacity(N_QUADS);
+/// Doubles the quad count, starting from [`MIN_QUAD_COUNT`], until frames take
+/// longer than the budget on average.
+#[derive(Debug)]
+struct StressTest {
+    budget_ms: f32,
+    warmup: u32,
+    /// Real frame times at the current count, in milliseconds.
+    frame_times: Vec<f32>,
+    /// Largest count that stayed within the budget, with its frame time.
+    best: Option<(usize, f32)>,
+}
 
-        let mut rng = rand::thread_rng();
-        for i in 0..(N_QUADS as u32) {
-            let quad = Quad::random(&mut rng, i, area_width);
-            vertices.push(quad.vertices(0.5));
-            indices.push(quad.indices(i));
-            quads.push(quad);
+impl StressTest {
+    fn new(budget_ms: f32) -> Self {
+        Self {
+            budget_ms,
+            warmup: STRESS_WARMUP_FRAMES,
+            frame_times: Vec::with_capacity(STRESS_SAMPLE_FRAMES),
+            best: None,
         }
+    }
+}
+
+impl Scene for RoundQuadsScene {
+    fn new(size: PhysicalSize<u32>, options: &SceneOptions) -> Result<Self, SceneError> {
+        let mode = options.quad_mode.unwrap_or_default();
+        let quad_count = match options.stress_budget_ms {
+            // where the stress test starts, so that the buffers are only
+            // created once
+            Some(_) if !options.vsync => MIN_QUAD_COUNT,
+            _ => (options.quad_count.unwrap_or(DEFAULT_QUAD_COUNT)).clamp(1, mode.max_quad_count()),
+        };
+        let area_width = Quad::area_width(quad_count);
+
+        let style = options.quad_style.unwrap_or_default();
+        let seed = options.seed.unwrap_or_else(rand::random);
+        let (quads, rng) = Self::random_quads(seed, quad_count, style);
 
         unsafe {
             // Normal blending
@@ -57,203 +226,893 @@ impl RoundQuadsScene {
             gl::BlendEquation(gl::FUNC_ADD);
             gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
 
-            let round_rect_shader = create_shader_program(SRC_VERT_ROUND_RECT, SRC_FRAG_ROUND_RECT);
+            let round_rect_shader = Self::create_shader(mode)?;
+            let update_shader = Self::create_update_shader(mode)?;
 
-            let u_mvp_quad = gl::GetUniformLocation(round_rect_shader, c"u_mvp".as_ptr());
+            let viewport = Vec2::new(size.width as f32, size.height as f32);
 
-            let mut vao: u32 = 0;
-            gl::GenVertexArrays(1, &mut vao);
-            gl::BindVertexArray(vao);
+            let mut scene = Self {
+                matrix: Mat4::default(),
+                viewport,
 
-            let mut ssbo: u32 = 0;
-            gl::GenBuffers(1, &mut ssbo);
-            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, ssbo);
+                mode,
+                round_rect_shader,
+                update_shader,
+                vao: VertexArray::new(),
+                vbo: Buffer::new(),
+                ebo: Buffer::new(),
+                index_count: 0,
 
-            let mut vbo: u32 = 0;
-            gl::GenBuffers(1, &mut vbo);
-            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
-            gl::BufferData(
-                gl::ARRAY_BUFFER,
-                mem::size_of_val(vertices.as_slice()) as GLsizeiptr,
-                vertices.as_slice().as_ptr() as *const _,
-                gl::DYNAMIC_DRAW,
-            );
+                quads,
+                vertices: Vec::new(),
+                instances: Vec::new(),
+                animation: None,
 
-            let mut ebo: u32 = 0;
-            gl::GenBuffers(1, &mut ebo);
-            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo);
-            gl::BufferData(
-                gl::ELEMENT_ARRAY_BUFFER,
-                mem::size_of_val(indices.as_slice()) as GLsizeiptr,
-                indices.as_slice().as_ptr() as *const _,
-                gl::STATIC_DRAW,
-            );
+                uploaded_bytes: 0,
 
-            let size_vertex = mem::size_of::<Vertex>() as GLsizei;
-            let size_f32 = mem::size_of::<f32>() as GLsizei;
-
-            #[rustfmt::skip]
-            {
-                let a_position      = gl::GetAttribLocation(round_rect_shader, c"position"      .as_ptr()) as GLuint;
-                let a_size          = gl::GetAttribLocation(round_rect_shader, c"size"          .as_ptr()) as GLuint;
-                let a_fill_color    = gl::GetAttribLocation(round_rect_shader, c"fill_color"    .as_ptr()) as GLuint;
-                let a_stroke_color  = gl::GetAttribLocation(round_rect_shader, c"stroke_color"  .as_ptr()) as GLuint;
-                let a_border_radius = gl::GetAttribLocation(round_rect_shader, c"border_radius" .as_ptr()) as GLuint;
-                let a_border_width  = gl::GetAttribLocation(round_rect_shader, c"border_width"  .as_ptr()) as GLuint;
-                let a_intensity     = gl::GetAttribLocation(round_rect_shader, c"intensity"     .as_ptr()) as GLuint;
-
-                gl::VertexAttribPointer(a_position,      2, gl::FLOAT, gl::FALSE, size_vertex,   0             as _);
-                gl::VertexAttribPointer(a_size,          2, gl::FLOAT, gl::FALSE, size_vertex, ( 2 * size_f32) as _);
-                gl::VertexAttribPointer(a_fill_color,    4, gl::FLOAT, gl::FALSE, size_vertex, ( 4 * size_f32) as _);
-                gl::VertexAttribPointer(a_stroke_color,  4, gl::FLOAT, gl::FALSE, size_vertex, ( 8 * size_f32) as _);
-                gl::VertexAttribPointer(a_border_radius, 1, gl::FLOAT, gl::FALSE, size_vertex, (12 * size_f32) as _);
-                gl::VertexAttribPointer(a_border_width,  1, gl::FLOAT, gl::FALSE, size_vertex, (13 * size_f32) as _);
-                gl::VertexAttribPointer(a_intensity,     1, gl::FLOAT, gl::FALSE, size_vertex, (14 * size_f32) as _);
-
-                gl::EnableVertexAttribArray(a_position      as GLuint);
-                gl::EnableVertexAttribArray(a_size          as GLuint);
-                gl::EnableVertexAttribArray(a_fill_color    as GLuint);
-                gl::EnableVertexAttribArray(a_stroke_color  as GLuint);
-                gl::EnableVertexAttribArray(a_border_radius as GLuint);
-                gl::EnableVertexAttribArray(a_border_width  as GLuint);
-                gl::EnableVertexAttribArray(a_intensity     as GLuint);
+                style,
+                seed,
+                rng,
+                area_width,
+
+                stress_test: None,
+                stress_budget_ms: options.stress_budget_ms.unwrap_or(DEFAULT_STRESS_BUDGET_MS),
+                vsync: options.vsync,
+
+                selected: None,
+                drag: None,
+                moved: Vec::new(),
             };
 
-            let win_size = window.inner_size();
-            let viewport = Vec2::new(win_size.width as f32, win_size.height as f32);
+            if let Err(e) = scene.recreate_buffers_or_resize(MIN_QUAD_COUNT.min(quad_count)) {
+                eprintln!("round quads: {quad_count} quads don't fit, {e}");
+            }
+            if options.stress_budget_ms.is_some() {
+                scene.start_stress_test();
+            }
 
-            Self {
-                matrix: Mat4::default(),
-                viewport,
+            Ok(scene)
+        }
+    }
 
-                round_rect_shader,
-                vao,
-                vbo,
-                ebo,
+    fn name() -> &'static str {
+        "Round Quads"
+    }
 
-                u_mvp_quad,
+    fn on_key(&mut self, keycode: Key<SmolStr>) {
+        match keycode {
+            Key::Named(NamedKey::ArrowUp) => {
+                let count =
+                    (self.quads.len() * 2).clamp(MIN_QUAD_COUNT, self.mode.max_quad_count());
+                if let Err(e) = self.set_quad_count(count) {
+                    eprintln!("round quads: {count} quads don't fit, {e}");
+                }
+            }
+            Key::Named(NamedKey::ArrowDown) => {
+                // stays under the minimum if the command line started there
+                let len = self.quads.len();
+                let count = (len / 2).max(MIN_QUAD_COUNT.min(len));
+                if let Err(e) = self.set_quad_count(count) {
+                    eprintln!("round quads: {count} quads don't fit, {e}");
+                }
+            }
+            Key::Character(ch) if ch.eq_ignore_ascii_case("m") => {
+                if let Err(e) = self.set_mode(self.mode.next()) {
+                    eprintln!("{e}");
+                }
+            }
+            Key::Character(ch) if ch.eq_ignore_ascii_case("s") => self.set_style(self.style.next()),
+            Key::Character(ch) if ch.eq_ignore_ascii_case("t") => {
+                if self.stress_test.take().is_some() {
+                    println!("stress test: stopped");
+                } else {
+                    self.start_stress_test();
+                }
+            }
+            _ => (),
+        }
+    }
 
-                quads,
-                vertices,
-                indices,
+    fn on_mouse_button(
+        &mut self,
+        camera: &Camera,
+        pointer: Vec2,
+        button: MouseButton,
+        state: ElementState,
+    ) -> bool {
+        if button != MouseButton::Left {
+            return false;
+        }
 
-                area_width,
+        let pos = camera.pointer_to_pos(pointer, self.viewport);
+
+        match state {
+            ElementState::Pressed => {
+                self.selected = self.pick(pos);
 
-                last_instant: Instant::now(),
+                // clicking next to the quads pans the camera instead
+                let Some(i) = self.selected else {
+                    return false;
+                };
+
+                println!("quad {i}: {}", self.quads[i]);
+                self.drag = Some(Drag {
+                    index: i,
+                    offset: self.quads[i].position - pos,
+                });
+                true
             }
+            ElementState::Released => self.drag.take().is_some(),
+        }
+    }
+
+    fn reload_shaders(&mut self) -> Result<(), ShaderError> {
+        unsafe {
+            let round_rect_shader = Self::create_shader(self.mode)?;
+            let update_shader = Self::create_update_shader(self.mode)?;
+
+            self.round_rect_shader = round_rect_shader;
+            self.update_shader = update_shader;
+
+            // attribute locations can change between compilations
+            self.bind_layouts();
+
+            self.round_rect_shader.bind();
+            self.round_rect_shader.set("u_mvp", self.matrix);
         }
+
+        Ok(())
+    }
+
+    fn params(&self) -> String {
+        let bytes_per_quad = match self.mode {
+            QuadMode::Vertices => mem::size_of::<[Vertex; 4]>(),
+            QuadMode::Instanced | QuadMode::Gpu => mem::size_of::<Instance>(),
+        };
+
+        let mut params = format!(
+            "n={} {} {} {}B/quad {:.1}KiB/frame",
+            self.quads.len(),
+            self.mode,
+            self.style,
+            bytes_per_quad,
+            self.uploaded_bytes as f32 / 1024.0
+        );
+
+        if let Some(stress_test) = &self.stress_test {
+            params += &format!(" stress<{:.1}ms", stress_test.budget_ms);
+        }
+
+        params
     }
 
-    pub fn draw(&mut self, camera: &Camera, mouse_pos: Vec2) {
-        let dt = self.last_instant.elapsed().as_secs_f32();
-        self.last_instant = Instant::now();
+    fn draw(&mut self, camera: &Camera, clock: &FrameClock, mouse_pos: Vec2) {
+        self.step_stress_test(clock);
+
+        let dt = clock.dt();
 
         // rotate surroundings of mouse
         let mouse_pos = camera.pointer_to_pos(mouse_pos, self.viewport);
-        let surround_radius = 320.0;
-        let surround_area = Vec2::splat(surround_radius);
+        self.drag_quad(mouse_pos);
+
+        if let (Some(animation), Some(update_shader)) = (&mut self.animation, &self.update_shader) {
+            unsafe {
+                let count = self.quads.len();
+                animation.update(
+                    update_shader,
+                    &mut self.vao,
+                    &mut self.vbo,
+                    count,
+                    mouse_pos,
+                    dt,
+                );
+            }
 
-        let aw = self.area_width;
-        let (x_beg, y_beg) = Quad::closest_grid_idx_from_pos(mouse_pos - surround_area, aw);
-        let (x_end, y_end) = Quad::closest_grid_idx_from_pos(mouse_pos + surround_area, aw);
+            self.uploaded_bytes = 0;
+            self.draw_with_clear_color(0.0, 0.0, 0.0, 0.5);
+            return;
+        }
 
-        for y in y_beg..=y_end {
-            for x in x_beg..=x_end {
-                let i = (y * self.area_width + x) as usize;
+        let rows = self.rows_around(mouse_pos);
 
-                if let Some(quad) = self.quads.get_mut(i) {
-                    let distance = Vec2::distance(quad.position, mouse_pos);
-                    let intensity = (surround_radius - distance).max(0.0) / surround_radius;
+        for i in rows.iter().cloned().flatten() {
+            let quad = &mut self.quads[i];
+            let distance = Vec2::distance(quad.position, mouse_pos);
+            let intensity = (SURROUND_RADIUS - distance).max(0.0) / SURROUND_RADIUS;
 
-                    quad.rotation += (dt * PI) * 2.0 * intensity;
-                    self.vertices[i] = quad.vertices(2.0 * intensity + 0.5);
-                }
-            }
+            quad.rotation += (dt * PI) * 2.0 * intensity;
+            self.write_quad(i, 2.0 * intensity + 0.5);
         }
 
-        self.update_vertices(x_beg, x_end, y_beg, y_end);
+        self.uploaded_bytes = 0;
+        self.update_vertices(&rows);
 
         self.draw_with_clear_color(0.0, 0.0, 0.0, 0.5);
 
         // reset intensity
-        for y in y_beg..=y_end {
-            for x in x_beg..=x_end {
-                let i = (y * self.area_width + x) as usize;
+        for i in rows.iter().cloned().flatten() {
+            self.write_quad(i, 0.5);
+        }
 
-                if let Some(quad) = self.quads.get_mut(i) {
-                    self.vertices[i] = quad.vertices(0.5);
-                }
+        // reset vertices (otherwise artifacts appear if the mouse moves too quickly)
+        self.update_vertices(&rows);
+    }
+
+    fn resize(&mut self, camera: &Camera, width: i32, height: i32) {
+        unsafe {
+            gl::Viewport(0, 0, width, height);
+
+            self.viewport = Vec2::new(width as f32, height as f32);
+            self.matrix = camera.matrix(self.viewport);
+
+            self.round_rect_shader.bind();
+            self.round_rect_shader.set("u_mvp", self.matrix);
+        }
+    }
+}
+
+impl RoundQuadsScene {
+    unsafe fn create_shader(mode: QuadMode) -> Result<ShaderProgram, ShaderError> {
+        let vert = match mode {
+            QuadMode::Vertices => &SRC_VERT_ROUND_RECT,
+            QuadMode::Instanced | QuadMode::Gpu => &SRC_VERT_ROUND_RECT_INSTANCED,
+        };
+
+        let [round_rect_shader] = create_programs([(vert, &SRC_FRAG_ROUND_RECT)])?;
+        Ok(round_rect_shader)
+    }
+
+    /// Program updating the quads in [`QuadMode::Gpu`], whose outputs are
+    /// captured in the same layout as [`Instance`].
+    unsafe fn create_update_shader(mode: QuadMode) -> Result<Option<ShaderProgram>, ShaderError> {
+        if mode != QuadMode::Gpu {
+            return Ok(None);
+        }
+
+        let varyings = (Instance::ATTRIBS.iter())
+            .map(|attrib| format!("next_{}", attrib.name))
+            .collect::<Vec<_>>();
+
+        let update_shader = build_feedback_program(&SRC_VERT_ROUND_QUADS_UPDATE, &varyings)?;
+
+        // recomputed from the mouse position every frame
+        update_shader.ignore("intensity");
+
+        Ok(Some(update_shader))
+    }
+
+    /// Switches to another mode, replacing the shaders and all the buffers.
+    fn set_mode(&mut self, mode: QuadMode) -> Result<(), ShaderError> {
+        unsafe {
+            let round_rect_shader = Self::create_shader(mode)?;
+            let update_shader = Self::create_update_shader(mode)?;
+
+            // pick up where the GPU left the quads
+            if self.animation.is_some() {
+                self.read_back_quads();
+            }
+
+            self.round_rect_shader = round_rect_shader;
+            self.update_shader = update_shader;
+            self.mode = mode;
+
+            let count = self.quads.len();
+            if count > mode.max_quad_count() {
+                self.resize_quads(mode.max_quad_count());
+            }
+            if let Err(e) = self.recreate_buffers_or_resize(MIN_QUAD_COUNT.min(count)) {
+                eprintln!("round quads: {count} quads don't fit in {mode} mode, {e}");
             }
         }
 
-        // reset vertices (otherwise artifacts appear if the mouse moves too quickly)
-        self.update_vertices(x_beg, x_end, y_beg, y_end);
+        println!("round quads mode: {mode}");
+        Ok(())
+    }
+
+    /// Adds or removes quads, and reallocates the buffers. Goes back to the
+    /// previous count if they don't fit.
+    fn set_quad_count(&mut self, count: usize) -> Result<(), OutOfMemory> {
+        let previous = self.quads.len();
+        if count == previous {
+            return Ok(());
+        }
+
+        unsafe {
+            if self.animation.is_some() {
+                self.read_back_quads();
+            }
+
+            self.resize_quads(count);
+            self.recreate_buffers_or_resize(previous)?;
+        }
+
+        println!("round quads: {count}");
+        Ok(())
+    }
+
+    /// Adds or removes quads at the end and lays all of them out again in a
+    /// square. The buffers are left to reallocate.
+    fn resize_quads(&mut self, count: usize) {
+        let area_width = Quad::area_width(count);
+        self.quads.truncate(count);
+        for i in self.quads.len()..count {
+            let quad = Quad::random(&mut self.rng, i as u32, area_width, self.style);
+            self.quads.push(quad);
+        }
+
+        for (i, quad) in self.quads.iter_mut().enumerate() {
+            quad.position = Quad::pos_from_idx(i as u32, area_width);
+        }
+        self.area_width = area_width;
+
+        self.selected = None;
+        self.drag = None;
+        self.moved.clear();
     }
 
-    fn update_vertices(&mut self, x_beg: u32, x_end: u32, y_beg: u32, y_end: u32) {
+    /// Creates every quad again from the seed in another style, which also
+    /// puts back the moved and spun ones.
+    fn set_style(&mut self, style: QuadStyle) {
+        let (quads, rng) = Self::random_quads(self.seed, self.quads.len(), style);
+        self.quads = quads;
+        self.rng = rng;
+        self.style = style;
+
+        self.selected = None;
+        self.drag = None;
+        self.moved.clear();
+
         unsafe {
-            gl::BindVertexArray(self.vao);
-            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
-            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ebo);
+            let count = self.quads.len();
+            if let Err(e) = self.recreate_buffers_or_resize(MIN_QUAD_COUNT.min(count)) {
+                eprintln!("round quads: {count} quads don't fit, {e}");
+            }
+        }
+
+        println!("round quads style: {style}");
+    }
+
+    /// Quads laid out in a square grid, with the generator left to add more.
+    fn random_quads(seed: u64, count: usize, style: QuadStyle) -> (Vec<Quad>, StdRng) {
+        let area_width = Quad::area_width(count);
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        let quads = (0..count as u32)
+            .map(|i| Quad::random(&mut rng, i, area_width, style))
+            .collect();
+
+        (quads, rng)
+    }
+
+    unsafe fn recreate_buffers(&mut self) -> Result<(), OutOfMemory> {
+        // the old objects are deleted first, so that both sets are never alive
+        self.animation = None;
+        self.vao = VertexArray::new();
+        self.vbo = Buffer::new();
+        self.ebo = Buffer::new();
+        self.create_buffers()
+    }
+
+    /// Reallocates the buffers, or resizes the quads to `fallback`, a count
+    /// known to fit, when they don't.
+    unsafe fn recreate_buffers_or_resize(&mut self, fallback: usize) -> Result<(), OutOfMemory> {
+        let Err(e) = self.recreate_buffers() else {
+            return Ok(());
+        };
+
+        self.resize_quads(fallback);
+        self.recreate_buffers()
+            .expect("the quads don't fit anymore after shrinking");
+        Err(e)
+    }
+
+    /// Number of rows of the grid, the last of which can be partial.
+    fn row_count(&self) -> u32 {
+        (self.quads.len() as u32).div_ceil(self.area_width)
+    }
+
+    fn start_stress_test(&mut self) {
+        if self.vsync {
+            eprintln!("stress test: frames wait for vertical sync, run with --no-vsync");
+            return;
+        }
+
+        println!(
+            "stress test: doubling quads from {MIN_QUAD_COUNT} until frames take over {:.2} ms",
+            self.stress_budget_ms
+        );
+
+        self.stress_test = Some(StressTest::new(self.stress_budget_ms));
+        if let Err(e) = self.set_quad_count(MIN_QUAD_COUNT) {
+            println!("stress test: stopped, {MIN_QUAD_COUNT} quads don't fit, {e}");
+            self.stress_test = None;
+        }
+    }
+
+    /// Records the last frame time, and once enough frames were averaged,
+    /// doubles the quad count or ends the test.
+    fn step_stress_test(&mut self, clock: &FrameClock) {
+        let Some(stress_test) = &mut self.stress_test else {
+            return;
+        };
+
+        if stress_test.warmup > 0 {
+            stress_test.warmup -= 1;
+            return;
+        }
+
+        stress_test.frame_times.push(clock.real_dt() * 1000.0);
+        if stress_test.frame_times.len() < STRESS_SAMPLE_FRAMES {
+            return;
+        }
+
+        let count = self.quads.len();
+        let avg_ms = stress_test.frame_times.iter().sum::<f32>() / STRESS_SAMPLE_FRAMES as f32;
+        stress_test.frame_times.clear();
+
+        let max_count = self.mode.max_quad_count();
+        if avg_ms <= stress_test.budget_ms && count < max_count {
+            stress_test.best = Some((count, avg_ms));
+            stress_test.warmup = STRESS_WARMUP_FRAMES;
+
+            let next_count = (count * 2).min(max_count);
+            let Err(e) = self.set_quad_count(next_count) else {
+                return;
+            };
+
+            // back to the count that fit
+            println!("stress test: stopped, {next_count} quads don't fit, {e}");
+            println!(
+                "stress test: max {count} quads at {avg_ms:.2} ms ({})",
+                self.mode
+            );
+            self.stress_test = None;
+            return;
+        }
+
+        let budget_ms = stress_test.budget_ms;
+        let best = if avg_ms <= budget_ms {
+            println!("stress test: reached the limit of {count} quads at {avg_ms:.2} ms");
+            Some((count, avg_ms))
+        } else {
+            println!("stress test: {count} quads took {avg_ms:.2} ms, over {budget_ms:.2} ms");
+            stress_test.best
+        };
+
+        self.stress_test = None;
+
+        match best {
+            Some((best_count, best_ms)) => {
+                println!(
+                    "stress test: max {best_count} quads at {best_ms:.2} ms ({})",
+                    self.mode
+                );
+                if let Err(e) = self.set_quad_count(best_count) {
+                    eprintln!("round quads: {best_count} quads don't fit, {e}");
+                }
+            }
+            None => println!(
+                "stress test: even {count} quads are over budget ({})",
+                self.mode
+            ),
+        }
+    }
 
-            for y in y_beg..=y_end {
-                let i_beg = (y * self.area_width + x_beg) as usize;
-                let i_end = (y * self.area_width + x_end) as usize;
+    /// Fills the buffers of the current mode with every quad at rest, and
+    /// empties the ones of the other modes.
+    unsafe fn create_buffers(&mut self) -> Result<(), OutOfMemory> {
+        gl::BindVertexArray(self.vao.id());
+        gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo.id());
+        gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ebo.id());
 
+        self.vertices = Vec::new();
+        self.instances = Vec::new();
+        self.animation = None;
+
+        let indices = match self.mode {
+            QuadMode::Vertices => {
+                self.vertices = try_collect(self.quads.iter().map(|quad| quad.vertices(0.5)))?;
+                buffer_data(gl::ARRAY_BUFFER, &self.vertices, gl::DYNAMIC_DRAW)?;
+
+                try_collect((0..self.quads.len() as u32).map(Quad::indices))?
+            }
+            QuadMode::Instanced => {
+                self.instances = try_collect(self.quads.iter().map(|quad| quad.instance(0.5)))?;
+                buffer_data(gl::ARRAY_BUFFER, &self.instances, gl::DYNAMIC_DRAW)?;
+
+                // the same quad for every instance
+                vec![Quad::indices(0)]
+            }
+            QuadMode::Gpu => {
+                let instances = try_collect(self.quads.iter().map(|quad| quad.instance(0.5)))?;
+                buffer_data(gl::ARRAY_BUFFER, &instances, gl::DYNAMIC_COPY)?;
+
+                self.animation = Some(GpuAnimation::new(&instances, &self.ebo)?);
+
+                vec![Quad::indices(0)]
+            }
+        };
+
+        gl::BindVertexArray(self.vao.id());
+        buffer_data(gl::ELEMENT_ARRAY_BUFFER, &indices, gl::STATIC_DRAW)?;
+        self.index_count = mem::size_of_val(indices.as_slice()) / mem::size_of::<u32>();
+
+        self.bind_layouts();
+
+        self.round_rect_shader.bind();
+        self.round_rect_shader.set("u_mvp", self.matrix);
+        Ok(())
+    }
+
+    unsafe fn bind_layouts(&self) {
+        gl::BindVertexArray(self.vao.id());
+        gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo.id());
+
+        match self.mode {
+            QuadMode::Vertices => self.round_rect_shader.bind_vertex_layout::<Vertex>(),
+            QuadMode::Instanced | QuadMode::Gpu => {
+                self.round_rect_shader.bind_instance_layout::<Instance>()
+            }
+        }
+
+        if let (Some(animation), Some(update_shader)) = (&self.animation, &self.update_shader) {
+            animation.bind_layouts(&self.round_rect_shader, update_shader, &self.vbo);
+        }
+    }
+
+    /// Copies the rotation of a quad integrated on the GPU back to it.
+    unsafe fn read_back_rotation(&mut self, i: usize) {
+        let offset = i * mem::size_of::<Instance>() + mem::offset_of!(Instance, rotation);
+
+        gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo.id());
+        gl::GetBufferSubData(
+            gl::ARRAY_BUFFER,
+            offset as GLsizeiptr,
+            mem::size_of::<f32>() as GLsizeiptr,
+            &mut self.quads[i].rotation as *mut f32 as *mut _,
+        );
+    }
+
+    /// Copies the rotations integrated on the GPU back to the quads.
+    unsafe fn read_back_quads(&mut self) {
+        let mut instances = vec![Instance::default(); self.quads.len()];
+
+        gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo.id());
+        gl::GetBufferSubData(
+            gl::ARRAY_BUFFER,
+            0,
+            mem::size_of_val(instances.as_slice()) as GLsizeiptr,
+            instances.as_mut_ptr() as *mut _,
+        );
+
+        for (quad, instance) in self.quads.iter_mut().zip(&instances) {
+            quad.rotation = instance.rotation;
+        }
+    }
+
+    /// Updates the CPU copy of a quad, to be uploaded by `update_vertices`.
+    fn write_quad(&mut self, i: usize, intensity: f32) {
+        let quad = self.quads[i];
+
+        match self.mode {
+            QuadMode::Vertices => self.vertices[i] = quad.vertices(intensity),
+            QuadMode::Instanced => self.instances[i] = quad.instance(intensity),
+            QuadMode::Gpu => unreachable!("quads are written on the GPU"),
+        }
+    }
+
+    /// Topmost quad under `pos`, in world coordinates.
+    fn pick(&mut self, pos: Vec2) -> Option<usize> {
+        let candidates = pick_candidates(pos, self.quads.len(), self.area_width, &self.moved);
+
+        if self.animation.is_some() {
+            for &i in &candidates {
+                unsafe { self.read_back_rotation(i) };
+            }
+        }
+
+        topmost_quad(&self.quads, &candidates, pos)
+    }
+
+    /// Moves the dragged quad to follow the mouse.
+    fn drag_quad(&mut self, mouse_pos: Vec2) {
+        let Some(Drag { index, offset }) = self.drag else {
+            return;
+        };
+
+        let position = mouse_pos + offset;
+        self.quads[index].position = position;
+
+        if !self.moved.contains(&index) {
+            self.moved.push(index);
+        }
+
+        // the other modes upload the moved quads with the others
+        if self.animation.is_some() {
+            unsafe {
+                let offset =
+                    index * mem::size_of::<Instance>() + mem::offset_of!(Instance, position);
+
+                gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo.id());
                 gl::BufferSubData(
                     gl::ARRAY_BUFFER,
-                    mem::size_of_val(&self.vertices[..i_beg]) as GLsizeiptr,
-                    mem::size_of_val(&self.vertices[i_beg..=i_end]) as GLsizeiptr,
-                    self.vertices[i_beg..=i_end].as_ptr() as *const _,
+                    offset as GLsizeiptr,
+                    mem::size_of::<Vec2>() as GLsizeiptr,
+                    &position as *const Vec2 as *const _,
                 );
             }
         }
     }
 
+    /// Ranges of the quads that can be close enough to `pos` to spin: the rows
+    /// of the grid around it, and each quad that was moved out of them.
+    fn rows_around(&self, pos: Vec2) -> Vec<RangeInclusive<usize>> {
+        let surround_area = Vec2::splat(SURROUND_RADIUS);
+
+        let aw = self.area_width;
+        let grid = (aw, self.row_count());
+        let (x_beg, y_beg) = Quad::closest_grid_idx_from_pos(pos - surround_area, grid);
+        let (x_end, y_end) = Quad::closest_grid_idx_from_pos(pos + surround_area, grid);
+
+        // the last row can be shorter than the others
+        let len = self.quads.len();
+        let mut rows = (y_beg..=y_end)
+            .filter_map(|y| {
+                let i_beg = (y * aw + x_beg) as usize;
+                let i_end = ((y * aw + x_end) as usize).min(len - 1);
+                (i_beg < len).then_some(i_beg..=i_end)
+            })
+            .collect::<Vec<_>>();
+
+        let in_rows = |i: usize| {
+            let (x, y) = (i as u32 % aw, i as u32 / aw);
+            (x_beg..=x_end).contains(&x) && (y_beg..=y_end).contains(&y)
+        };
+
+        let moved = self.moved.iter().filter(|&&i| !in_rows(i));
+        rows.extend(moved.map(|&i| i..=i));
+        rows
+    }
+
+    fn update_vertices(&mut self, rows: &[RangeInclusive<usize>]) {
+        unsafe {
+            push_debug_group(c"Upload vertices");
+
+            gl::BindVertexArray(self.vao.id());
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo.id());
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ebo.id());
+
+            let rows = rows.iter().cloned();
+            self.uploaded_bytes += match self.mode {
+                QuadMode::Vertices => upload_rows(&self.vertices, rows),
+                QuadMode::Instanced => upload_rows(&self.instances, rows),
+                QuadMode::Gpu => 0,
+            };
+
+            pop_debug_group();
+        }
+    }
+
     fn draw_with_clear_color(&self, r: GLfloat, g: GLfloat, b: GLfloat, a: GLfloat) {
         unsafe {
-            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            push_debug_group(c"Draw quads");
 
-            gl::BindVertexArray(self.vao);
-            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
-            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ebo);
+            bind_screen_framebuffer();
+
+            gl::BindVertexArray(self.vao.id());
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo.id());
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ebo.id());
 
             gl::ClearColor(r, g, b, a);
             gl::Clear(gl::COLOR_BUFFER_BIT);
 
-            gl::UseProgram(self.round_rect_shader);
-            gl::DrawElements(
-                gl::TRIANGLES,
-                mem::size_of_val(self.indices.as_slice()) as GLsizei,
-                gl::UNSIGNED_INT,
-                std::ptr::null(),
-            );
+            self.round_rect_shader.bind();
+            let selected = self.selected.map_or(-1, |i| i as i32);
+            self.round_rect_shader.set("u_selected", selected);
+
+            match self.mode {
+                QuadMode::Vertices => gl::DrawElements(
+                    gl::TRIANGLES,
+                    self.index_count as GLsizei,
+                    gl::UNSIGNED_INT,
+                    std::ptr::null(),
+                ),
+                QuadMode::Instanced | QuadMode::Gpu => gl::DrawElementsInstanced(
+                    gl::TRIANGLES,
+                    self.index_count as GLsizei,
+                    gl::UNSIGNED_INT,
+                    std::ptr::null(),
+                    self.quads.len() as GLsizei,
+                ),
+            }
+
+            pop_debug_group();
         }
     }
+}
 
-    pub fn resize(&mut self, camera: &Camera, width: i32, height: i32) {
-        unsafe {
-            gl::Viewport(0, 0, width, height);
-
-            self.viewport = Vec2::new(width as f32, height as f32);
-            self.matrix = camera.matrix(self.viewport);
+impl fmt::Display for Quad {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hex = |color: u32| {
+            let [r, g, b, a] = color.to_le_bytes();
+            format!("#{r:02x}{g:02x}{b:02x}{a:02x}")
+        };
+
+        let [tl, tr, br, bl] = self.corner_radii.to_array();
+        write!(
+            f,
+            "position ({:.1}, {:.1}), size {:.1}x{:.1}, rotation {:.1}°, \
+             radii ({tl:.2}, {tr:.2}, {br:.2}, {bl:.2}), border {:.2}, fill {}",
+            self.position.x,
+            self.position.y,
+            self.size.x,
+            self.size.y,
+            self.rotation.to_degrees().rem_euclid(360.0),
+            self.border_width,
+            hex(self.fill_color),
+        )?;
+
+        match self.gradient {
+            Gradient::None => (),
+            Gradient::Linear { angle, end_color } => {
+                write!(f, " to {} at {:.0}°", hex(end_color), angle.to_degrees())?
+            }
+            Gradient::Radial { end_color } => write!(f, " to {} radially", hex(end_color))?,
+        }
 
-            gl::UseProgram(self.round_rect_shader);
-            gl::UniformMatrix4fv(self.u_mvp_quad, 1, gl::FALSE, self.matrix.as_ref().as_ptr());
+        write!(f, ", stroke {}", hex(self.stroke_color))?;
+
+        if let Some(shadow) = self.shadow {
+            write!(
+                f,
+                ", {} shadow {} offset ({:.1}, {:.1}) blur {:.1}",
+                if shadow.inset { "inset" } else { "outer" },
+                hex(shadow.color),
+                shadow.offset.x,
+                shadow.offset.y,
+                shadow.blur,
+            )?;
         }
+
+        Ok(())
     }
 }
 
-impl Drop for RoundQuadsScene {
-    fn drop(&mut self) {
-        unsafe {
-            gl::DeleteProgram(self.round_rect_shader);
-            gl::DeleteVertexArrays(1, &self.vao);
+// Modified based on https://iquilezles.org/articles/distfunctions2d/, like in
+// common/round-rect.glsl
+fn sd_rounded_box(pos: Vec2, size: Vec2, radii: Vec4) -> f32 {
+    let (top, bottom) = if pos.x > 0.0 {
+        (radii.y, radii.z)
+    } else {
+        (radii.x, radii.w)
+    };
+    let radius = if pos.y > 0.0 { bottom } else { top };
+
+    let q = pos.abs() - size * 0.5 + radius;
+    q.max_element().min(0.0) + q.max(Vec2::ZERO).length() - radius
+}
 
-            let buffers = &[self.vbo, self.ebo];
-            gl::DeleteBuffers(1, buffers.as_ptr());
+/// Quads that can be under `pos`: the ones around its place in the grid, and
+/// the moved ones.
+fn pick_candidates(pos: Vec2, count: usize, area_width: u32, moved: &[usize]) -> Vec<usize> {
+    let aw = area_width;
+    let row_count = (count as u32).div_ceil(aw);
+    let (x, y) = Quad::closest_grid_idx_from_pos(pos, (aw, row_count));
+
+    // even rotated, quads don't reach further than the next grid cells
+    let mut candidates = Vec::with_capacity(9 + moved.len());
+    for ny in y.saturating_sub(1)..=y + 1 {
+        for nx in x.saturating_sub(1)..=(x + 1).min(aw - 1) {
+            let i = (ny * aw + nx) as usize;
+            if i < count && !moved.contains(&i) {
+                candidates.push(i);
+            }
         }
     }
+    candidates.extend(moved);
+    candidates
+}
+
+/// Topmost of the `candidates` that contains `pos`.
+fn topmost_quad(quads: &[Quad], candidates: &[usize], pos: Vec2) -> Option<usize> {
+    // quads are drawn in order, so the last one is on top
+    (candidates.iter().copied())
+        .filter(|&i| quads[i].contains(pos))
+        .max()
+}
+
+/// State of [`QuadMode::Gpu`]. Every frame, a transform feedback pass with the
+/// scene's update shader reads the quads from the buffer they were last drawn
+/// from and writes them spun into another one, then the two buffers are
+/// swapped.
+struct GpuAnimation {
+    /// Reads the scene's vertex buffer with the update shader.
+    update_vao: VertexArray,
+
+    /// Buffer the next state of the quads is written to.
+    next_vbo: Buffer,
+    /// Draws `next_vbo`.
+    next_vao: VertexArray,
+    /// Reads `next_vbo` with the update shader.
+    next_update_vao: VertexArray,
+}
+
+impl GpuAnimation {
+    /// Allocates the second buffer, sized for `instances`. The layouts are
+    /// bound afterwards with [`Self::bind_layouts`].
+    unsafe fn new(instances: &[Instance], ebo: &Buffer) -> Result<Self, OutOfMemory> {
+        let next_vbo = Buffer::new();
+        gl::BindBuffer(gl::ARRAY_BUFFER, next_vbo.id());
+        buffer_data(gl::ARRAY_BUFFER, instances, gl::DYNAMIC_COPY)?;
+
+        let next_vao = VertexArray::new();
+        gl::BindVertexArray(next_vao.id());
+        gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo.id());
+
+        Ok(Self {
+            update_vao: VertexArray::new(),
+
+            next_vbo,
+            next_vao,
+            next_update_vao: VertexArray::new(),
+        })
+    }
+
+    /// Binds the layouts of every VAO but the scene's, which draws `vbo`.
+    unsafe fn bind_layouts(
+        &self,
+        draw_shader: &ShaderProgram,
+        update_shader: &ShaderProgram,
+        vbo: &Buffer,
+    ) {
+        gl::BindVertexArray(self.next_vao.id());
+        gl::BindBuffer(gl::ARRAY_BUFFER, self.next_vbo.id());
+        draw_shader.bind_instance_layout::<Instance>();
+
+        gl::BindVertexArray(self.update_vao.id());
+        gl::BindBuffer(gl::ARRAY_BUFFER, vbo.id());
+        update_shader.bind_vertex_layout::<Instance>();
+
+        gl::BindVertexArray(self.next_update_vao.id());
+        gl::BindBuffer(gl::ARRAY_BUFFER, self.next_vbo.id());
+        update_shader.bind_vertex_layout::<Instance>();
+    }
+
+    /// Spins the quads of `vbo` into the other buffer, then swaps `vao` and
+    /// `vbo` with it so that they draw the new state.
+    unsafe fn update(
+        &mut self,
+        update_shader: &ShaderProgram,
+        vao: &mut VertexArray,
+        vbo: &mut Buffer,
+        count: usize,
+        mouse_pos: Vec2,
+        dt: f32,
+    ) {
+        push_debug_group(c"Animate quads");
+
+        update_shader.bind();
+        update_shader.set("u_mouse_pos", mouse_pos);
+        update_shader.set("u_surround_radius", SURROUND_RADIUS);
+        update_shader.set("u_dt", dt);
+
+        gl::Enable(gl::RASTERIZER_DISCARD);
+        gl::BindVertexArray(self.update_vao.id());
+        gl::BindBufferBase(gl::TRANSFORM_FEEDBACK_BUFFER, 0, self.next_vbo.id());
+
+        gl::BeginTransformFeedback(gl::POINTS);
+        gl::DrawArrays(gl::POINTS, 0, count as GLsizei);
+        gl::EndTransformFeedback();
+
+        gl::BindBufferBase(gl::TRANSFORM_FEEDBACK_BUFFER, 0, 0);
+        gl::Disable(gl::RASTERIZER_DISCARD);
+
+        mem::swap(vbo, &mut self.next_vbo);
+        mem::swap(vao, &mut self.next_vao);
+        mem::swap(&mut self.update_vao, &mut self.next_update_vao);
+
+        pop_debug_group();
+    }
 }
 
 #[repr(C)]
@@ -262,12 +1121,49 @@ struct Quad {
     pub position: Vec2,
     pub size: Vec2,
     pub rotation: f32,
-    pub border_radius: f32,
+    /// Radii of the top-left, top-right, bottom-right and bottom-left corners.
+    pub corner_radii: Vec4,
     pub border_width: f32,
     pub fill_color: u32,
+    pub gradient: Gradient,
     pub stroke_color: u32,
+    pub shadow: Option<Shadow>,
+}
+
+/// How a quad is filled from its `fill_color`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Gradient {
+    None,
+    /// To `end_color` on the other side of the quad, along `angle` in radians
+    /// in the frame of the quad.
+    Linear {
+        angle: f32,
+        end_color: u32,
+    },
+    /// To `end_color` at the middle of the sides.
+    Radial {
+        end_color: u32,
+    },
+}
+
+/// Shadow computed from the distance to the quad, as if it was blurred by a
+/// Gaussian.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Shadow {
+    color: u32,
+    /// In world coordinates, so that the shadow doesn't spin with the quad.
+    offset: Vec2,
+    /// Standard deviation of the blur.
+    blur: f32,
+    /// Inside the quad, as if it was a hole, instead of behind it.
+    inset: bool,
 }
 
+// Bits of the style of a record, like in common/round-rect.glsl
+const GRADIENT_LINEAR: u32 = 1;
+const GRADIENT_RADIAL: u32 = 2;
+const INSET_SHADOW: u32 = 4;
+
 impl Quad {
     fn pos_from_idx(i: u32, area_width: u32) -> Vec2 {
         Self::pos_from_grid_idx((i % area_width, i / area_width), area_width)
@@ -277,36 +1173,139 @@ impl Quad {
         (vec2(x as f32, y as f32) - area_width as f32 * 0.5) * 16.0
     }
 
-    fn closest_grid_idx_from_pos(pos: Vec2, area_width: u32) -> (u32, u32) {
+    /// Whether `pos` is inside the rounded box, with the distance that
+    /// `round-rect.frag` computes.
+    fn contains(&self, pos: Vec2) -> bool {
+        let unrotate = vec2(self.rotation.cos(), -self.rotation.sin());
+        let local = (pos - self.position).rotate(unrotate);
+
+        sd_rounded_box(local, self.size, self.corner_radii) <= 0.0
+    }
+
+    /// Quads per row, for a grid that is as square as possible.
+    fn area_width(quad_count: usize) -> u32 {
+        ((quad_count as f32).sqrt() as u32).max(1)
+    }
+
+    fn closest_grid_idx_from_pos(pos: Vec2, (area_width, row_count): (u32, u32)) -> (u32, u32) {
         let width = area_width as f32;
-        let upper_limit = width - 1.0;
 
         let pos = pos / 16.0 + width * 0.5;
         (
-            pos.x.round().clamp(0.0, upper_limit) as u32,
-            pos.y.round().clamp(0.0, upper_limit) as u32,
+            pos.x.round().clamp(0.0, width - 1.0) as u32,
+            pos.y.round().clamp(0.0, row_count as f32 - 1.0) as u32,
         )
     }
 
-    fn random(rng: &mut impl Rng, i: u32, area_width: u32) -> Self {
-        Self {
+    /// The plain quad is drawn from `rng` first, so that it's the same in
+    /// every style.
+    fn random(rng: &mut impl Rng, i: u32, area_width: u32, style: QuadStyle) -> Self {
+        let mut quad = Self {
             position: Self::pos_from_idx(i, area_width),
             size: vec2(rng.gen_range(10.0..=20.0), rng.gen_range(10.0..=20.0)),
             rotation: rng.gen_range(0.0..TAU),
-            border_radius: rng.gen_range(1.0..=5.0),
+            corner_radii: Vec4::splat(rng.gen_range(1.0..=5.0)),
             border_width: rng.gen_range(1.0..=5.0),
-            fill_color: u32::from_le_bytes([
-                rng.gen_range(128..=255),
-                rng.gen_range(128..=255),
-                rng.gen_range(128..=255),
-                rng.gen_range(128..=255),
-            ]),
-            stroke_color: u32::from_le_bytes([
-                rng.gen_range(24..=128),
-                rng.gen_range(24..=128),
-                rng.gen_range(24..=128),
-                rng.gen_range(128..=255),
-            ]),
+            fill_color: random_color(rng, 128..=255, 128..=255),
+            gradient: Gradient::None,
+            stroke_color: random_color(rng, 24..=128, 128..=255),
+            shadow: None,
+        };
+
+        match style {
+            QuadStyle::Plain => (),
+            QuadStyle::Cards => {
+                let [r, g, b, a] = quad.fill_color.to_le_bytes();
+                let darken = |c: u8| (c as f32 * 0.6) as u8;
+
+                quad.corner_radii = vec4(5.0, 5.0, 1.0, 1.0);
+                quad.gradient = Gradient::Linear {
+                    angle: PI * 0.5,
+                    end_color: u32::from_le_bytes([darken(r), darken(g), darken(b), a]),
+                };
+                quad.shadow = Some(Shadow {
+                    color: 0x80000000,
+                    offset: vec2(0.0, 2.0),
+                    blur: 2.0,
+                    inset: false,
+                });
+            }
+            QuadStyle::Random => {
+                let max_radius = quad.size.min_element() * 0.5;
+                quad.corner_radii =
+                    Vec4::from_array([(); 4].map(|_| rng.gen_range(0.0..=max_radius)));
+
+                quad.gradient = match rng.gen_range(0..3) {
+                    0 => Gradient::None,
+                    1 => Gradient::Linear {
+                        angle: rng.gen_range(0.0..TAU),
+                        end_color: random_color(rng, 128..=255, 128..=255),
+                    },
+                    _ => Gradient::Radial {
+                        end_color: random_color(rng, 128..=255, 128..=255),
+                    },
+                };
+
+                quad.shadow = match rng.gen_range(0..3) {
+                    0 => None,
+                    n => Some(Shadow {
+                        color: random_color(rng, 0..=32, 96..=224),
+                        offset: vec2(rng.gen_range(-2.0..=2.0), rng.gen_range(0.0..=3.0)),
+                        blur: rng.gen_range(0.5..=3.0),
+                        inset: n == 2,
+                    }),
+                };
+            }
+        }
+
+        quad
+    }
+
+    /// Bits of the gradient and shadow kinds in the records.
+    fn style_bits(&self) -> u32 {
+        let gradient = match self.gradient {
+            Gradient::None => 0,
+            Gradient::Linear { .. } => GRADIENT_LINEAR,
+            Gradient::Radial { .. } => GRADIENT_RADIAL,
+        };
+
+        match self.shadow {
+            Some(Shadow { inset: true, .. }) => gradient | INSET_SHADOW,
+            _ => gradient,
+        }
+    }
+
+    /// End color and angle of the gradient in the records.
+    fn gradient_params(&self) -> (u32, f32) {
+        match self.gradient {
+            Gradient::None => (self.fill_color, 0.0),
+            Gradient::Linear { angle, end_color } => (end_color, angle),
+            Gradient::Radial { end_color } => (end_color, 0.0),
+        }
+    }
+
+    /// Color, offset and blur of the shadow in the records, fully transparent
+    /// when there is none.
+    fn shadow_params(&self) -> (u32, Vec2, f32) {
+        match self.shadow {
+            Some(Shadow {
+                color,
+                offset,
+                blur,
+                ..
+            }) => (color, offset, blur),
+            None => (0, Vec2::ZERO, 0.0),
+        }
+    }
+
+    /// How far the outer shadow reaches past the quad, like `shadow_margin`
+    /// in common/round-rect.glsl.
+    fn shadow_margin(&self) -> f32 {
+        match self.shadow {
+            Some(shadow) if !shadow.inset && shadow.color >> 24 != 0 => {
+                3.0 * shadow.blur + shadow.offset.length()
+            }
+            _ => 0.0,
         }
     }
 
@@ -315,47 +1314,309 @@ impl Quad {
             position,
             size,
             rotation,
-            border_radius,
+            corner_radii,
             border_width,
             fill_color,
             stroke_color,
+            ..
         } = self;
 
+        let (gradient_color, gradient_angle) = self.gradient_params();
+        let (shadow_color, shadow_offset, shadow_blur) = self.shadow_params();
+
         let r = vec2(rotation.cos(), rotation.sin());
+        // grown to cover the outer shadow
+        let extent = size + 2.0 * self.shadow_margin();
 
         #[rustfmt::skip]
         let pos_dims = [
-            ((vec2(-0.5, -0.5) * size).rotate(r)) + position,
-            ((vec2(-0.5,  0.5) * size).rotate(r)) + position,
-            ((vec2( 0.5,  0.5) * size).rotate(r)) + position,
-            ((vec2( 0.5, -0.5) * size).rotate(r)) + position,
+            ((vec2(-0.5, -0.5) * extent).rotate(r)) + position,
+            ((vec2(-0.5,  0.5) * extent).rotate(r)) + position,
+            ((vec2( 0.5,  0.5) * extent).rotate(r)) + position,
+            ((vec2( 0.5, -0.5) * extent).rotate(r)) + position,
         ];
 
         pos_dims.map(|position| Vertex {
             position,
             size,
-            fill_color: Vec4::from_array(fill_color.to_le_bytes().map(|n| n as f32)) / 255.0,
-            stroke_color: Vec4::from_array(stroke_color.to_le_bytes().map(|n| n as f32)) / 255.0,
-            border_radius,
+            corner_radii,
             border_width,
+            fill_color: unpack_color(fill_color),
+            gradient_color: unpack_color(gradient_color),
+            gradient_angle,
+            style: self.style_bits(),
+            stroke_color: unpack_color(stroke_color),
+            shadow_color: unpack_color(shadow_color),
+            shadow_offset: shadow_offset.rotate(vec2(r.x, -r.y)),
+            shadow_blur,
             intensity,
         })
     }
 
-    fn indices(&self, quad_index: u32) -> [u32; 6] {
+    fn instance(self, intensity: f32) -> Instance {
+        let Self {
+            position,
+            size,
+            rotation,
+            corner_radii,
+            border_width,
+            fill_color,
+            stroke_color,
+            ..
+        } = self;
+
+        let (gradient_color, gradient_angle) = self.gradient_params();
+        let (shadow_color, shadow_offset, shadow_blur) = self.shadow_params();
+
+        Instance {
+            corner_radii,
+            position,
+            size,
+            rotation,
+            border_width,
+            fill_color,
+            gradient_color,
+            gradient_angle,
+            style: self.style_bits(),
+            stroke_color,
+            shadow_color,
+            shadow_offset,
+            shadow_blur,
+            intensity,
+        }
+    }
+
+    fn indices(quad_index: u32) -> [u32; 6] {
         let i = quad_index * 4;
         [i, 1 + i, 2 + i, i, 2 + i, 3 + i]
     }
 }
 
-#[repr(C)]
-#[derive(Debug, Clone, Copy, Default)]
-struct Vertex {
-    position: Vec2,
-    size: Vec2,
-    fill_color: Vec4,
-    stroke_color: Vec4,
-    border_radius: f32,
-    border_width: f32,
-    intensity: f32,
+/// RGBA packed in little-endian order, red in the lowest byte.
+fn random_color(rng: &mut impl Rng, rgb: RangeInclusive<u8>, alpha: RangeInclusive<u8>) -> u32 {
+    u32::from_le_bytes([
+        rng.gen_range(rgb.clone()),
+        rng.gen_range(rgb.clone()),
+        rng.gen_range(rgb),
+        rng.gen_range(alpha),
+    ])
+}
+
+fn unpack_color(color: u32) -> Vec4 {
+    Vec4::from_array(color.to_le_bytes().map(|n| n as f32)) / 255.0
+}
+
+vertex_layout! {
+    #[derive(Debug, Clone, Copy, Default)]
+    struct Vertex {
+        position: Vec2,
+        size: Vec2,
+        corner_radii: Vec4,
+        border_width: f32,
+        fill_color: Vec4,
+        gradient_color: Vec4,
+        gradient_angle: f32,
+        style: u32,
+        stroke_color: Vec4,
+        shadow_color: Vec4,
+        // in the frame of the quad
+        shadow_offset: Vec2,
+        shadow_blur: f32,
+        intensity: f32,
+    }
+}
+
+vertex_layout! {
+    /// Quad drawn in [`QuadMode::Instanced`], with its colors still packed.
+    #[derive(Debug, Clone, Copy, Default)]
+    struct Instance {
+        // first, because transform feedback writes the records without the
+        // padding that its alignment would need anywhere else
+        corner_radii: Vec4,
+        position: Vec2,
+        size: Vec2,
+        rotation: f32,
+        border_width: f32,
+        fill_color: u32,
+        gradient_color: u32,
+        gradient_angle: f32,
+        style: u32,
+        stroke_color: u32,
+        shadow_color: u32,
+        shadow_offset: Vec2,
+        shadow_blur: f32,
+        intensity: f32,
+    }
+}
+
+/// Replaces the whole buffer bound to `target` with `data`, unless the driver
+/// can't allocate it.
+unsafe fn buffer_data<T>(target: GLenum, data: &[T], usage: GLenum) -> Result<(), OutOfMemory> {
+    // errors left by earlier calls would be taken for this one's
+    while gl::GetError() != gl::NO_ERROR {}
+
+    gl::BufferData(
+        target,
+        mem::size_of_val(data) as GLsizeiptr,
+        data.as_ptr() as *const _,
+        usage,
+    );
+
+    match gl::GetError() {
+        gl::OUT_OF_MEMORY => Err(OutOfMemory(mem::size_of_val(data))),
+        _ => Ok(()),
+    }
+}
+
+/// Collects records into a vector, failing instead of aborting when they don't
+/// fit in memory.
+fn try_collect<T>(records: impl ExactSizeIterator<Item = T>) -> Result<Vec<T>, OutOfMemory> {
+    let mut vec = Vec::new();
+    vec.try_reserve_exact(records.len())
+        .map_err(|_| OutOfMemory(records.len() * mem::size_of::<T>()))?;
+    vec.extend(records);
+    Ok(vec)
+}
+
+/// Bytes of quads that couldn't be allocated, on the CPU or the GPU.
+#[derive(Debug)]
+struct OutOfMemory(usize);
+
+impl fmt::Display for OutOfMemory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "out of memory for {:.1} MiB",
+            self.0 as f32 / (1 << 20) as f32
+        )
+    }
+}
+
+/// Uploads ranges of `records` to the same place in the buffer bound to
+/// `GL_ARRAY_BUFFER`, one call per range. Returns the number of bytes uploaded.
+unsafe fn upload_rows<T>(
+    records: &[T],
+    rows: impl Iterator<Item = RangeInclusive<usize>>,
+) -> usize {
+    let mut uploaded = 0;
+
+    for row in rows {
+        let (i_beg, i_end) = (*row.start(), *row.end());
+
+        gl::BufferSubData(
+            gl::ARRAY_BUFFER,
+            mem::size_of_val(&records[..i_beg]) as GLsizeiptr,
+            mem::size_of_val(&records[i_beg..=i_end]) as GLsizeiptr,
+            records[i_beg..=i_end].as_ptr() as *const _,
+        );
+
+        uploaded += mem::size_of_val(&records[i_beg..=i_end]);
+    }
+
+    uploaded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quad(position: Vec2, size: Vec2, rotation: f32, corner_radii: Vec4) -> Quad {
+        Quad {
+            position,
+            size,
+            rotation,
+            corner_radii,
+            border_width: 1.0,
+            fill_color: 0xffffffff,
+            gradient: Gradient::None,
+            stroke_color: 0xff000000,
+            shadow: None,
+        }
+    }
+
+    /// Picks among `quads` laid out in a grid of `area_width`.
+    fn pick(quads: &[Quad], area_width: u32, moved: &[usize], pos: Vec2) -> Option<usize> {
+        let candidates = pick_candidates(pos, quads.len(), area_width, moved);
+        topmost_quad(quads, &candidates, pos)
+    }
+
+    #[test]
+    fn contains_edges() {
+        let quad = quad(Vec2::ZERO, vec2(20.0, 10.0), 0.0, Vec4::splat(4.0));
+
+        assert!(quad.contains(Vec2::ZERO));
+        assert!(quad.contains(vec2(9.9, 0.0)));
+        assert!(quad.contains(vec2(0.0, -4.9)));
+        assert!(!quad.contains(vec2(10.1, 0.0)));
+        assert!(!quad.contains(vec2(0.0, 5.1)));
+    }
+
+    #[test]
+    fn contains_corners() {
+        // square top-left corner, round others
+        let quad = quad(Vec2::ZERO, vec2(20.0, 10.0), 0.0, vec4(0.0, 4.0, 4.0, 4.0));
+
+        assert!(quad.contains(vec2(-9.9, -4.9)));
+        // outside the rounding, inside the bounds
+        assert!(!quad.contains(vec2(9.5, -4.5)));
+        assert!(!quad.contains(vec2(9.5, 4.5)));
+        assert!(!quad.contains(vec2(-9.5, 4.5)));
+        // inside the rounding
+        assert!(quad.contains(vec2(8.0, 3.0)));
+    }
+
+    #[test]
+    fn contains_rotated() {
+        let quad = quad(
+            vec2(100.0, 50.0),
+            vec2(20.0, 10.0),
+            PI / 2.0,
+            Vec4::splat(1.0),
+        );
+
+        assert!(quad.contains(vec2(100.0, 59.0)));
+        assert!(quad.contains(vec2(104.0, 50.0)));
+        assert!(!quad.contains(vec2(109.0, 50.0)));
+    }
+
+    #[test]
+    fn contains_scaled() {
+        let small = quad(Vec2::ZERO, vec2(10.0, 10.0), PI / 4.0, Vec4::splat(2.0));
+        let large = quad(Vec2::ZERO, vec2(40.0, 40.0), PI / 4.0, Vec4::splat(8.0));
+
+        // along the diagonal, which the rotation turned into an axis
+        let pos = vec2(10.0, 10.0);
+        assert!(!small.contains(pos));
+        assert!(large.contains(pos));
+        // past the rounding of the large one's corner
+        assert!(!large.contains(vec2(27.5, 0.0)));
+    }
+
+    #[test]
+    fn pick_topmost() {
+        let area_width = 2;
+        let mut quads = (0..4)
+            .map(|i| {
+                let position = Quad::pos_from_idx(i, area_width);
+                quad(position, vec2(10.0, 10.0), 0.0, Vec4::splat(2.0))
+            })
+            .collect::<Vec<_>>();
+
+        // overlaps the next quad halfway
+        quads[0].size = vec2(30.0, 10.0);
+        let overlap = (quads[0].position + quads[1].position) * 0.5 + vec2(4.0, 0.0);
+
+        assert_eq!(pick(&quads, area_width, &[], quads[0].position), Some(0));
+        assert_eq!(pick(&quads, area_width, &[], overlap), Some(1));
+        assert_eq!(pick(&quads, area_width, &[], quads[2].position + 8.0), None);
+
+        // moved on top of an earlier one, and out of the grid
+        quads[3].position = quads[0].position;
+        assert_eq!(pick(&quads, area_width, &[3], quads[0].position), Some(3));
+        quads[2].position = vec2(500.0, 500.0);
+        assert_eq!(
+            pick(&quads, area_width, &[2, 3], vec2(500.0, 500.0)),
+            Some(2)
+        );
+    }
 }
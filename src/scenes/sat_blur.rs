@@ -0,0 +1,166 @@
+use std::ffi::CStr;
+
+use glam::{IVec2, UVec2};
+use winit::keyboard::{Key, NamedKey, SmolStr};
+
+use crate::common_gl::{
+    pop_debug_group, push_debug_group, ColorFormat, Framebuffer, FramebufferDesc, ShaderError,
+    ShaderProgram,
+};
+
+use super::blur_pipeline::{BlurPipeline, BlurTechnique, Level, PassRunner};
+use super::{
+    create_programs, BlurOptions, SceneEntry, SRC_FRAG_SAT_BLUR, SRC_FRAG_SAT_BUILD,
+    SRC_VERT_SCREEN,
+};
+
+pub type SatBlurScene = BlurPipeline<SatBlur>;
+
+pub const SCENE: SceneEntry = SceneEntry::new::<SatBlurScene>();
+
+const MAX_RADIUS: f32 = 64.0;
+
+/// Box blur read from a summed-area table, which costs the same whatever the
+/// radius, so that it can also change from one pixel to the next.
+pub struct SatBlur {
+    build_shader: ShaderProgram,
+    blur_shader: ShaderProgram,
+
+    /// Ping-pong targets of the passes building the table. The sums need
+    /// 32-bit floats, whatever the format of the pyramid.
+    tables: Option<[Framebuffer; 2]>,
+
+    radius: f32,
+    is_radial: bool,
+}
+
+impl BlurTechnique for SatBlur {
+    const NAME: &'static str = "SAT Blur";
+    const DEFAULT_LAYERS: usize = 1;
+    const BLURS_FIRST_LEVEL: bool = true;
+    const SEPARABLE: bool = false;
+    // a single pass blurs with any radius
+    const MAX_LAYERS: usize = 1;
+
+    const DOWNSAMPLING_LABEL: &'static CStr = c"SAT blur";
+    const UPSAMPLING_LABEL: &'static CStr = c"SAT upsampling";
+
+    fn new(options: &BlurOptions) -> Result<Self, ShaderError> {
+        let [build_shader, blur_shader] = unsafe { Self::create_programs()? };
+
+        Ok(Self {
+            build_shader,
+            blur_shader,
+
+            tables: None,
+
+            radius: options.radius.unwrap_or(8.0).clamp(0.0, MAX_RADIUS),
+            is_radial: false,
+        })
+    }
+
+    fn reload_shaders(&mut self) -> Result<(), ShaderError> {
+        let [build_shader, blur_shader] = unsafe { Self::create_programs()? };
+        self.build_shader = build_shader;
+        self.blur_shader = blur_shader;
+        Ok(())
+    }
+
+    fn programs(&self) -> Vec<&ShaderProgram> {
+        vec![&self.build_shader, &self.blur_shader]
+    }
+
+    unsafe fn create_targets(&mut self, size: UVec2) {
+        self.tables = None;
+
+        let create = |name| {
+            FramebufferDesc::new(name, size)
+                .color_format(ColorFormat::Rgba32F)
+                .filter(gl::NEAREST)
+                .create()
+        };
+
+        match create("sat_ping").and_then(|ping| Ok([ping, create("sat_pong")?])) {
+            Ok(tables) => self.tables = Some(tables),
+            Err(e) => eprintln!("Warning: {e}, the SAT blur is disabled"),
+        }
+    }
+
+    fn on_key(&mut self, keycode: &Key<SmolStr>) -> bool {
+        match keycode {
+            Key::Named(NamedKey::ArrowRight) => {
+                self.radius = (self.radius + 0.5).min(MAX_RADIUS);
+            }
+            Key::Named(NamedKey::ArrowLeft) => {
+                self.radius = (self.radius - 0.5).max(0.0);
+            }
+            Key::Character(ch) if ch == "/" => {
+                self.is_radial = !self.is_radial;
+            }
+            _ => return false,
+        }
+
+        true
+    }
+
+    fn params(&self, layers: usize) -> String {
+        let mode = if self.is_radial { "radial" } else { "uniform" };
+        format!("r={:.2} l={} {}", self.radius, layers, mode)
+    }
+
+    unsafe fn downsample(&self, runner: &PassRunner, from: &Framebuffer, to: &Level) {
+        let Some(tables) = &self.tables else {
+            return;
+        };
+
+        let table = self.build_table(runner, from, tables);
+
+        push_debug_group(c"SAT sampling");
+        self.blur_shader.bind();
+        self.blur_shader.set("u_radius", self.radius);
+        self.blur_shader.set("u_radial", self.is_radial);
+        runner.draw(table.texture(), &to.fb);
+        pop_debug_group();
+    }
+}
+
+impl SatBlur {
+    unsafe fn create_programs() -> Result<[ShaderProgram; 2], ShaderError> {
+        create_programs([
+            (&SRC_VERT_SCREEN, &SRC_FRAG_SAT_BUILD),
+            (&SRC_VERT_SCREEN, &SRC_FRAG_SAT_BLUR),
+        ])
+    }
+
+    /// Sums the image along rows, then along columns, doubling the distance
+    /// between the added texels every pass. Returns the table holding the
+    /// result.
+    unsafe fn build_table<'a>(
+        &self,
+        runner: &PassRunner,
+        image: &'a Framebuffer,
+        tables: &'a [Framebuffer; 2],
+    ) -> &'a Framebuffer {
+        push_debug_group(c"SAT build");
+        self.build_shader.bind();
+
+        let mut input = image;
+        let mut output = 0;
+
+        for (axis, len) in [(IVec2::X, image.size.x), (IVec2::Y, image.size.y)] {
+            let mut offset = 1;
+            while offset < len {
+                self.build_shader.set("u_offset", axis * offset as i32);
+                self.build_shader.set("u_first", std::ptr::eq(input, image));
+                runner.draw(input.texture(), &tables[output]);
+
+                input = &tables[output];
+                output = 1 - output;
+                offset *= 2;
+            }
+        }
+
+        pop_debug_group();
+        input
+    }
+}
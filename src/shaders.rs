@@ -0,0 +1,219 @@
+//! Shader sources, embedded in the binary and reloaded from `assets/shaders`
+//! while developing.
+//!
+//! Hot-reloading only exists in debug builds. When it's enabled, sources are
+//! read from disk instead of using the embedded ones, and [`ShaderWatcher`]
+//! tells when one of them changed so that scenes can recompile their programs.
+//!
+//! Sources can `#include` the files of [`INCLUDES`], and a [`ShaderSource`] can
+//! add its own `#define`s to build a variant of a shader.
+
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::common_gl::{
+    create_feedback_program, create_shader_program, preprocess_shader, ShaderCode, ShaderError,
+    ShaderProgram, ShaderStage,
+};
+
+pub const SHADER_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/shaders");
+
+static HOT_RELOAD: AtomicBool = AtomicBool::new(false);
+
+/// Embeds a shader from `assets/shaders`.
+macro_rules! shader_source {
+    ($file:literal) => {
+        $crate::shaders::ShaderSource::new(
+            $file,
+            include_bytes!(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/assets/shaders/",
+                $file
+            )),
+        )
+    };
+}
+
+pub(crate) use shader_source;
+
+/// Files that shaders can `#include`, relative to `assets/shaders`.
+pub const INCLUDES: &[ShaderSource] = &[
+    shader_source!("common/dither.glsl"),
+    shader_source!("common/premult.glsl"),
+    shader_source!("common/round-rect.glsl"),
+];
+
+pub struct ShaderSource {
+    /// File name in `assets/shaders`.
+    pub name: &'static str,
+    embedded: &'static [u8],
+    defines: &'static [(&'static str, &'static str)],
+}
+
+impl ShaderSource {
+    pub const fn new(name: &'static str, embedded: &'static [u8]) -> Self {
+        Self {
+            name,
+            embedded,
+            defines: &[],
+        }
+    }
+
+    /// Variant of the shader with these `#define`s.
+    pub const fn with_defines(self, defines: &'static [(&'static str, &'static str)]) -> Self {
+        Self { defines, ..self }
+    }
+
+    /// Reads the source from disk when hot-reloading, otherwise returns the
+    /// embedded one.
+    pub fn load(&self) -> Cow<'static, [u8]> {
+        if !is_hot_reload_enabled() {
+            return Cow::Borrowed(self.embedded);
+        }
+
+        match fs::read(self.path()) {
+            Ok(source) => Cow::Owned(source),
+            Err(e) => {
+                eprintln!("Error reading shader {}: {e}", self.name);
+                Cow::Borrowed(self.embedded)
+            }
+        }
+    }
+
+    pub fn path(&self) -> PathBuf {
+        Path::new(SHADER_DIR).join(self.name)
+    }
+
+    /// Loads the source with its includes and defines.
+    pub fn preprocess(&self) -> Result<ShaderCode, String> {
+        preprocess_shader(self.name, &self.load(), self.defines, &load_include)
+    }
+}
+
+/// Builds a program from a vertex and a fragment shader.
+///
+/// # Safety
+///
+/// Needs a current GL context, like the functions of `common_gl`.
+pub unsafe fn build_program(
+    vert: &ShaderSource,
+    frag: &ShaderSource,
+) -> Result<ShaderProgram, ShaderError> {
+    let preprocess = |source: &ShaderSource, stage| {
+        source
+            .preprocess()
+            .map_err(|log| ShaderError::new(stage, log, None))
+    };
+
+    let vert_code = preprocess(vert, ShaderStage::Vertex);
+    let frag_code = preprocess(frag, ShaderStage::Fragment);
+
+    vert_code
+        .and_then(|vert_code| create_shader_program(&vert_code, &frag_code?))
+        .map_err(|e| e.with_names(vert.name, frag.name))
+}
+
+/// Builds a program that only has a vertex shader, whose outputs named in
+/// `varyings` are captured by transform feedback.
+///
+/// # Safety
+///
+/// Needs a current GL context, like the functions of `common_gl`.
+pub unsafe fn build_feedback_program(
+    vert: &ShaderSource,
+    varyings: &[impl AsRef<str>],
+) -> Result<ShaderProgram, ShaderError> {
+    vert.preprocess()
+        .map_err(|log| ShaderError::new(ShaderStage::Vertex, log, None))
+        .and_then(|vert_code| create_feedback_program(&vert_code, varyings))
+        .map_err(|e| e.with_names(vert.name, "transform feedback"))
+}
+
+fn load_include(name: &str) -> Option<Vec<u8>> {
+    if is_hot_reload_enabled() {
+        return fs::read(Path::new(SHADER_DIR).join(name)).ok();
+    }
+
+    let include = INCLUDES.iter().find(|include| include.name == name)?;
+    Some(include.embedded.to_vec())
+}
+
+pub fn is_hot_reload_enabled() -> bool {
+    HOT_RELOAD.load(Ordering::Relaxed)
+}
+
+/// Enables hot-reloading, which does nothing in release builds.
+pub fn enable_hot_reload() {
+    if cfg!(debug_assertions) {
+        HOT_RELOAD.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Polls the modification times of the files in `assets/shaders`.
+pub struct ShaderWatcher {
+    mtimes: HashMap<PathBuf, SystemTime>,
+    last_poll: Instant,
+}
+
+impl ShaderWatcher {
+    const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+    pub fn new() -> Self {
+        Self {
+            mtimes: read_mtimes(),
+            last_poll: Instant::now(),
+        }
+    }
+
+    /// Whether a shader changed since the last call. Only checks the disk a few
+    /// times per second.
+    pub fn poll(&mut self) -> bool {
+        if !is_hot_reload_enabled() || self.last_poll.elapsed() < Self::POLL_INTERVAL {
+            return false;
+        }
+
+        self.last_poll = Instant::now();
+
+        let mtimes = read_mtimes();
+        let changed = mtimes != self.mtimes;
+        self.mtimes = mtimes;
+        changed
+    }
+}
+
+impl Default for ShaderWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn read_mtimes() -> HashMap<PathBuf, SystemTime> {
+    let mut mtimes = HashMap::new();
+    read_dir_mtimes(Path::new(SHADER_DIR), &mut mtimes);
+    mtimes
+}
+
+/// Also goes into subdirectories, where the included files are.
+fn read_dir_mtimes(dir: &Path, mtimes: &mut HashMap<PathBuf, SystemTime>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+
+        if meta.is_dir() {
+            read_dir_mtimes(&entry.path(), mtimes);
+        } else if let Ok(mtime) = meta.modified() {
+            mtimes.insert(entry.path(), mtime);
+        }
+    }
+}